@@ -1,10 +1,20 @@
 //! Typed IPC layer - Tauri commands for frontend
 
-use idea_engine_core::adapters::{AnthropicAdapter, GeminiAdapter, OpenAIAdapter};
-use idea_engine_core::{Orchestrator, Storage};
+use idea_engine_core::adapters::{adapter_for, base_url_for, target_label, ProviderAdapter, Target};
+use idea_engine_core::catalog;
+use idea_engine_core::clarify::{clarifying_questions, fold_answers, ClarifyingAnswer};
+use idea_engine_core::context::{chat_context, DEFAULT_CONTEXT_TOKENS};
+use idea_engine_core::decompose::HierarchicalPlan;
+use idea_engine_core::drill::drill_down as run_drill_down;
+use idea_engine_core::orchestrator::OrchestratorResult;
+use idea_engine_core::persona::{builtin_personas, resolve_personas, PersonaSpec};
+use idea_engine_core::storage::{message_bundles, IdeaRef, SpendGroup, UsageScope};
+use idea_engine_core::usage::{self, Usage};
+use idea_engine_core::{CompletionRequest, GenerationParams, Orchestrator, Storage};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,12 +24,78 @@ pub struct CreateChatInput {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SendMessageInput {
     pub chat_id: String,
     pub content: String,
     pub system_prompt: String,
     pub providers: Vec<String>,
+    /// Explicit (provider, model) pairs with their own params; when set, `providers` is ignored
+    #[serde(default)]
+    pub targets: Vec<Target>,
     pub rubric: Option<[f64; 6]>,
+    /// Follow-up requests allowed per provider to fix a bundle that fails validation
+    #[serde(default)]
+    pub repair_attempts: Option<u32>,
+    /// Temperature, max tokens, seed, top_p and stop sequences; unset fields keep provider defaults
+    #[serde(default)]
+    pub params: Option<GenerationParams>,
+    /// Token budget for earlier turns of the chat sent as context
+    #[serde(default)]
+    pub context_tokens: Option<usize>,
+    /// Have the first provider split the problem into sub-problems, solve each and assemble one plan
+    #[serde(default)]
+    pub decompose: bool,
+    /// Answers to clarifying questions, folded into the problem before it is sent
+    #[serde(default)]
+    pub clarifications: Vec<ClarifyingAnswer>,
+    /// Built-in persona ids or custom `{id, name, overlay}` personas; each provider runs once per persona
+    #[serde(default)]
+    pub personas: Vec<PersonaSpec>,
+}
+
+/// Check a problem for missing details before sending it. Without a provider only the local
+/// heuristic runs; with one, its (cheap) model is asked and the heuristic is the fallback.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClarifyInput {
+    pub content: String,
+    /// Chat the model call's spend is recorded against; none before the chat exists
+    #[serde(default)]
+    pub chat_id: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Expand one idea of a stored bundle; the sub-plan is stored as a child of that idea
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrillDownInput {
+    pub message_id: String,
+    pub bundle_id: String,
+    pub idea_index: usize,
+    pub system_prompt: String,
+    pub providers: Vec<String>,
+    #[serde(default)]
+    pub targets: Vec<Target>,
+    pub rubric: Option<[f64; 6]>,
+    #[serde(default)]
+    pub repair_attempts: Option<u32>,
+    #[serde(default)]
+    pub params: Option<GenerationParams>,
+    #[serde(default)]
+    pub context_tokens: Option<usize>,
+}
+
+/// Spend summed per day, provider, provider:model, recipe or chat, optionally from a unix time on
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendInput {
+    pub group_by: SpendGroup,
+    #[serde(default)]
+    pub since: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,6 +112,10 @@ pub struct RecipeInput {
     pub user_prompt_template: String,
     pub rubric_json: String,
     pub few_shot_examples_json: Option<String>,
+    #[serde(default)]
+    pub assertions_json: Option<String>,
+    #[serde(default)]
+    pub params_json: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,6 +139,80 @@ fn get_api_key(provider: &str) -> Option<String> {
         .and_then(|e| e.get_password().ok())
 }
 
+/// Targets for a run: the explicit ones, or each named provider at its default model
+fn run_targets(providers: &[String], targets: &[Target]) -> Vec<Target> {
+    if targets.is_empty() {
+        providers.iter().map(|p| Target::new(p, None)).collect()
+    } else {
+        targets.to_vec()
+    }
+}
+
+/// Orchestrator over the targets' adapters, each carrying its own params. Errors on an unknown provider, a repeated provider:model pair or no usable target.
+fn target_orchestrator(targets: &[Target]) -> Result<Orchestrator, String> {
+    let mut adapters: Vec<Arc<dyn ProviderAdapter>> = Vec::new();
+    let mut params = Vec::new();
+    let mut labels = HashSet::new();
+    for target in targets {
+        let adapter = adapter_for(&target.provider, target.model.as_deref(), get_api_key(&target.provider))
+            .ok_or_else(|| format!("Unknown provider: {}", target.provider))?;
+        let label = target_label(&*adapter);
+        if !labels.insert(label.clone()) {
+            return Err(format!("Target listed twice: {}", label));
+        }
+        adapters.push(adapter);
+        params.push(target.params.clone());
+    }
+    if adapters.is_empty() {
+        return Err("No providers enabled. Add API keys in Settings.".to_string());
+    }
+    Ok(Orchestrator::new(adapters).with_adapter_params(params))
+}
+
+/// `{"provider:model": {"bundleIds": [...], "mergedInto": [...], "errors": [...]}}` for a run
+fn targets_json(result: &OrchestratorResult) -> serde_json::Value {
+    result
+        .by_target()
+        .into_iter()
+        .map(|(target, outcome)| {
+            (
+                target,
+                serde_json::json!({
+                    "bundleIds": outcome.bundle_ids,
+                    "mergedInto": outcome.merged_into,
+                    "errors": outcome.errors,
+                    "usage": outcome.usage,
+                }),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// A decomposed answer's plan; sections point at bundles by id since the bundles travel as `ideaBundles`
+fn plan_json(plan: &HierarchicalPlan) -> serde_json::Value {
+    serde_json::json!({
+        "sections": plan.sections.iter().map(|s| serde_json::json!({
+            "subProblem": {
+                "id": s.sub_problem.id,
+                "title": s.sub_problem.title,
+                "description": s.sub_problem.description,
+                "dependsOn": s.sub_problem.depends_on,
+            },
+            "bundleId": s.bundle.as_ref().map(|b| &b.id),
+            "errors": s.errors,
+        })).collect::<Vec<_>>(),
+        "steps": plan.steps.iter().map(|s| serde_json::json!({
+            "order": s.order,
+            "subProblemId": s.sub_problem_id,
+            "action": s.action,
+            "details": s.details,
+        })).collect::<Vec<_>>(),
+        "warnings": plan.warnings,
+        "usage": plan.usage(),
+    })
+}
+
 #[tauri::command]
 pub fn create_chat(state: State<Arc<Storage>>, input: CreateChatInput) -> Result<String, String> {
     let id = Uuid::new_v4().to_string();
@@ -100,6 +254,12 @@ pub fn get_chat_messages(state: State<Arc<Storage>>, chat_id: String) -> Result<
                 "content": r.content,
                 "ideaBundles": r.idea_bundles_json.and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok()),
                 "feedback": r.feedback,
+                "parent": r.parent.map(|p| serde_json::json!({
+                    "messageId": p.message_id,
+                    "bundleId": p.bundle_id,
+                    "ideaIndex": p.idea_index,
+                })),
+                "plan": r.plan_json.and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok()),
                 "createdAt": r.created_at,
             })
         })
@@ -110,93 +270,162 @@ pub fn get_chat_messages(state: State<Arc<Storage>>, chat_id: String) -> Result<
 pub async fn send_message(state: State<'_, Arc<Storage>>, input: SendMessageInput) -> Result<serde_json::Value, String> {
     let msg_id = Uuid::new_v4().to_string();
 
+    // Earlier turns, read before this message is stored
+    let history = chat_context(
+        get_storage(&state),
+        &input.chat_id,
+        input.context_tokens.unwrap_or(DEFAULT_CONTEXT_TOKENS),
+    )?;
+    let content = fold_answers(&input.content, &input.clarifications);
     get_storage(&state)
-        .insert_message(&msg_id, &input.chat_id, "user", &input.content, None)
+        .insert_message(&msg_id, &input.chat_id, "user", &content, None)
         .map_err(|e| e.to_string())?;
 
-    let mut adapters: Vec<Arc<dyn idea_engine_core::adapters::ProviderAdapter>> = Vec::new();
-    for p in &input.providers {
-        match p.as_str() {
-            "openai" => {
-                adapters.push(Arc::new(OpenAIAdapter::new(get_api_key("openai"))));
-            }
-            "anthropic" => {
-                adapters.push(Arc::new(AnthropicAdapter::new(get_api_key("anthropic"))));
-            }
-            "gemini" => {
-                adapters.push(Arc::new(GeminiAdapter::new(get_api_key("gemini"))));
-            }
-            _ => {}
-        }
-    }
-
-    if adapters.is_empty() {
-        return Err("No providers enabled. Add API keys in Settings.".to_string());
-    }
-
-    let orchestrator = Orchestrator::new(adapters);
-    let result = orchestrator
-        .run(&input.system_prompt, &input.content, input.rubric.as_ref())
-        .await;
-
-    let bundles_json: Vec<serde_json::Value> = result
-        .bundles
-        .iter()
-        .map(|b| {
-            serde_json::json!({
-                "id": b.id,
-                "provider": b.provider,
-                "model": b.model,
-                "ideas": b.ideas,
-                "stepPlan": b.step_plan,
-                "risks": b.risks,
-                "dependencies": b.dependencies,
-                "effort": b.effort,
-                "nextActions": b.next_actions,
-                "createdAt": b.created_at,
-            })
-        })
-        .collect();
-
-    let assistant_content = if result.bundles.is_empty() {
-        format!(
-            "No ideas generated. Errors: {}",
-            result
-                .errors
-                .iter()
-                .map(|(p, e)| format!("{}: {}", p, e))
-                .collect::<Vec<_>>()
-                .join("; ")
-        )
+    let targets = run_targets(&input.providers, &input.targets);
+    let orchestrator = target_orchestrator(&targets)?
+        .with_repair_attempts(input.repair_attempts.unwrap_or(0))
+        .with_personas(resolve_personas(&input.personas)?);
+    let request = CompletionRequest::new(&input.system_prompt, &content)
+        .with_history(history)
+        .with_params(input.params.unwrap_or_default());
+    let (assistant_content, bundles_json, errors, by_target, attempts, plan) = if input.decompose {
+        let planner = orchestrator.planner().ok_or("No providers enabled")?;
+        let plan = orchestrator.run_decomposed(planner, &request, input.rubric.as_ref()).await;
+        let errors: Vec<(String, String)> = plan.sections.iter().flat_map(|s| s.errors.clone()).collect();
+        let plan_json = plan_json(&plan);
+        (plan.summary(), plan.bundles_json(), errors, None, plan.attempts, Some(plan_json))
     } else {
-        format!(
-            "Generated {} idea bundle(s). {}",
-            result.bundles.len(),
-            result
-                .errors
-                .iter()
-                .map(|(p, e)| format!("{}: {}", p, e))
-                .collect::<Vec<_>>()
-                .join("; ")
-        )
-        .trim_end_matches("; ")
-        .to_string()
+        let result = orchestrator.run(&request, input.rubric.as_ref()).await;
+        let by_target = targets_json(&result);
+        (result.summary(), message_bundles(&result.bundles), result.errors, Some(by_target), result.attempts, None)
     };
 
-    let bundles_str = serde_json::to_string(&bundles_json).unwrap();
+    let bundles_str = bundles_json.to_string();
     let asst_id = Uuid::new_v4().to_string();
     get_storage(&state)
         .insert_message(&asst_id, &input.chat_id, "assistant", &assistant_content, Some(&bundles_str))
         .map_err(|e| e.to_string())?;
+    if let Some(plan) = &plan {
+        get_storage(&state)
+            .set_message_plan(&asst_id, &plan.to_string())
+            .map_err(|e| e.to_string())?;
+    }
+    let scope = UsageScope {
+        chat_id: Some(input.chat_id.clone()),
+        message_id: Some(asst_id.clone()),
+        ..Default::default()
+    };
+    get_storage(&state)
+        .record_usage(&attempts, &scope)
+        .map_err(|e| e.to_string())?;
 
     Ok(serde_json::json!({
         "messageId": asst_id,
         "content": assistant_content,
         "ideaBundles": bundles_json,
-        "errors": result.errors,
+        "errors": errors,
+        "targets": by_target,
+        "usage": Usage::from_attempts(&attempts),
+        "plan": plan,
+    }))
+}
+
+#[tauri::command]
+pub fn list_personas() -> Result<serde_json::Value, String> {
+    serde_json::to_value(builtin_personas()).map_err(|e| e.to_string())
+}
+
+/// Catalog entries for the model selector, optionally for one provider
+#[tauri::command]
+pub fn list_models(provider: Option<String>) -> Result<serde_json::Value, String> {
+    let catalog = catalog::catalog();
+    let models: Vec<_> = catalog
+        .models()
+        .iter()
+        .filter(|m| provider.as_deref().is_none_or(|p| m.provider == p))
+        .collect();
+    serde_json::to_value(models).map_err(|e| e.to_string())
+}
+
+/// Pull a provider's model list into the catalog override file in the app data dir
+#[tauri::command]
+pub async fn refresh_models(app: AppHandle, provider: String) -> Result<serde_json::Value, String> {
+    let api_key = get_api_key(&provider).ok_or("Missing API key")?;
+    let path = app.path().app_data_dir().map_err(|e| e.to_string())?.join(catalog::OVERRIDE_FILE);
+    let report = catalog::refresh(&provider, &api_key, base_url_for(&provider).as_deref(), &path).await?;
+    serde_json::to_value(report).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clarify_problem(state: State<'_, Arc<Storage>>, input: ClarifyInput) -> Result<serde_json::Value, String> {
+    let adapter = match &input.provider {
+        Some(provider) => Some(
+            adapter_for(provider, input.model.as_deref(), get_api_key(provider))
+                .ok_or_else(|| format!("Unknown provider: {}", provider))?,
+        ),
+        None => None,
+    };
+    let ((questions, source, error), attempts) =
+        usage::track(clarifying_questions(adapter.as_deref(), &input.content)).await;
+    let scope = UsageScope {
+        chat_id: input.chat_id.clone(),
+        ..Default::default()
+    };
+    get_storage(&state)
+        .record_usage(&attempts, &scope)
+        .map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "questions": questions,
+        "source": source,
+        "error": error,
+        "usage": Usage::from_attempts(&attempts),
     }))
 }
 
+#[tauri::command]
+pub async fn drill_down(state: State<'_, Arc<Storage>>, input: DrillDownInput) -> Result<serde_json::Value, String> {
+    let orchestrator = target_orchestrator(&run_targets(&input.providers, &input.targets))?
+        .with_repair_attempts(input.repair_attempts.unwrap_or(0));
+    let target = IdeaRef {
+        message_id: input.message_id,
+        bundle_id: input.bundle_id,
+        idea_index: input.idea_index,
+    };
+    let drill = run_drill_down(
+        get_storage(&state),
+        &orchestrator,
+        &target,
+        &input.system_prompt,
+        input.params.unwrap_or_default(),
+        input.rubric.as_ref(),
+        input.context_tokens.unwrap_or(DEFAULT_CONTEXT_TOKENS),
+    )
+    .await?;
+
+    Ok(serde_json::json!({
+        "promptMessageId": drill.prompt_message_id,
+        "messageId": drill.message_id,
+        "content": drill.content,
+        "ideaBundles": message_bundles(&drill.result.bundles),
+        "errors": drill.result.errors,
+        "targets": targets_json(&drill.result),
+        "usage": drill.result.usage(),
+        "parent": {
+            "messageId": target.message_id,
+            "bundleId": target.bundle_id,
+            "ideaIndex": target.idea_index,
+        },
+    }))
+}
+
+#[tauri::command]
+pub fn get_spend(state: State<Arc<Storage>>, input: SpendInput) -> Result<serde_json::Value, String> {
+    let rows = get_storage(&state)
+        .spend(input.group_by, input.since)
+        .map_err(|e| e.to_string())?;
+    serde_json::to_value(rows).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn set_feedback(state: State<Arc<Storage>>, input: SetFeedbackInput) -> Result<(), String> {
     get_storage(&state)
@@ -217,6 +446,8 @@ pub fn list_recipes(state: State<Arc<Storage>>) -> Result<Vec<serde_json::Value>
                 "userPromptTemplate": r.user_prompt_template,
                 "rubricJson": r.rubric_json,
                 "fewShotExamplesJson": r.few_shot_examples_json,
+                "assertionsJson": r.assertions_json,
+                "paramsJson": r.params_json,
                 "createdAt": r.created_at,
             })
         })
@@ -225,6 +456,8 @@ pub fn list_recipes(state: State<Arc<Storage>>) -> Result<Vec<serde_json::Value>
 
 #[tauri::command]
 pub fn save_recipe(state: State<Arc<Storage>>, input: RecipeInput) -> Result<(), String> {
+    idea_engine_core::eval::assertions::parse_assertions(input.assertions_json.as_deref())?;
+    GenerationParams::from_json(input.params_json.as_deref())?;
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
@@ -237,6 +470,8 @@ pub fn save_recipe(state: State<Arc<Storage>>, input: RecipeInput) -> Result<(),
         user_prompt_template: input.user_prompt_template,
         rubric_json: input.rubric_json,
         few_shot_examples_json: input.few_shot_examples_json,
+        assertions_json: input.assertions_json,
+        params_json: input.params_json,
         created_at: now,
     };
     get_storage(&state)
@@ -259,3 +494,88 @@ pub fn set_api_key(input: SetApiKeyInput) -> Result<(), String> {
     let entry = keyring::Entry::new(&service, "api-key").map_err(|e| e.to_string())?;
     entry.set_password(&input.key).map_err(|e| e.to_string())
 }
+
+#[tauri::command]
+pub fn list_eval_comparisons(state: State<Arc<Storage>>) -> Result<Vec<serde_json::Value>, String> {
+    let rows = get_storage(&state).list_eval_comparisons().map_err(|e| e.to_string())?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            serde_json::json!({
+                "id": r.id,
+                "labelA": r.label_a,
+                "labelB": r.label_b,
+                "bBeatsA": r.b_beats_a,
+                "createdAt": r.created_at,
+            })
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub fn get_eval_comparison(state: State<Arc<Storage>>, id: String) -> Result<serde_json::Value, String> {
+    let c = idea_engine_core::eval::ab::load_comparison(get_storage(&state), &id)?;
+    let arm = |a: &idea_engine_core::eval::ab::AbArm| {
+        serde_json::json!({
+            "recipeId": a.recipe_id,
+            "provider": a.provider,
+            "model": a.model,
+        })
+    };
+    let dimensions: Vec<serde_json::Value> = c
+        .dimensions
+        .iter()
+        .map(|d| {
+            serde_json::json!({
+                "dimension": d.dimension,
+                "meanA": d.mean_a,
+                "ciA": d.ci_a,
+                "meanB": d.mean_b,
+                "ciB": d.ci_b,
+                "meanDiff": d.mean_diff,
+                "ciDiff": d.ci_diff,
+                "pValue": d.p_value,
+                "pAdjusted": d.p_adjusted,
+                "verdict": d.verdict,
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({
+        "id": c.id,
+        "armA": arm(&c.arm_a),
+        "armB": arm(&c.arm_b),
+        "options": {
+            "repeats": c.options.repeats,
+            "alpha": c.options.alpha,
+            "bootstrapIterations": c.options.bootstrap_iterations,
+            "seed": c.options.seed,
+        },
+        "runIdA": c.run_id_a,
+        "runIdB": c.run_id_b,
+        "pairedProblems": c.paired_problems,
+        "pairs": c.pairs,
+        "dimensions": dimensions,
+        "warnings": c.warnings,
+        "bBeatsA": c.b_beats_a,
+        "createdAt": c.created_at,
+    }))
+}
+
+#[tauri::command]
+pub fn get_eval_leaderboard(state: State<Arc<Storage>>) -> Result<Vec<serde_json::Value>, String> {
+    let ratings = idea_engine_core::eval::tournament::leaderboard(get_storage(&state))?;
+    Ok(ratings
+        .into_iter()
+        .map(|r| {
+            serde_json::json!({
+                "contestant": r.contestant,
+                "elo": r.elo,
+                "bradleyTerry": r.bradley_terry,
+                "matches": r.matches,
+                "wins": r.wins,
+                "losses": r.losses,
+                "ties": r.ties,
+            })
+        })
+        .collect())
+}
@@ -1,5 +1,6 @@
 mod ipc;
 
+use idea_engine_core::catalog::{self, ModelCatalog};
 use idea_engine_core::Storage;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -16,6 +17,12 @@ pub fn run() {
             let db_path = app_data.join("idea_engine.db");
             let storage = Storage::new(&db_path)?;
             app.manage(Arc::new(storage));
+            // User edits and refreshed listings on top of the bundled catalog, under any IDEA_ENGINE_MODELS
+            // file; a broken file is ignored
+            let models = ModelCatalog::load(Some(&app_data.join(catalog::OVERRIDE_FILE)))
+                .or_else(|_| ModelCatalog::load(None))
+                .unwrap_or_else(|_| ModelCatalog::bundled());
+            catalog::install(models);
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -23,11 +30,20 @@ pub fn run() {
             ipc::list_chats,
             ipc::get_chat_messages,
             ipc::send_message,
+            ipc::clarify_problem,
+            ipc::list_personas,
+            ipc::list_models,
+            ipc::refresh_models,
+            ipc::drill_down,
             ipc::set_feedback,
+            ipc::get_spend,
             ipc::list_recipes,
             ipc::save_recipe,
             ipc::get_api_keys,
             ipc::set_api_key,
+            ipc::list_eval_comparisons,
+            ipc::get_eval_comparison,
+            ipc::get_eval_leaderboard,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -29,7 +29,7 @@ interface ChatTimelineProps {
 export function ChatTimeline({ chatId }: ChatTimelineProps) {
   const queryClient = useQueryClient();
   const [input, setInput] = useState('');
-  const { selectedProviders, setSelectedProviders } = useChatStore();
+  const { selectedProviders, setSelectedProviders, selectedModels, setSelectedModel } = useChatStore();
   const apiCtx = useApiContext();
 
   const { data: messages = [] } = useQuery({
@@ -40,7 +40,15 @@ export function ChatTimeline({ chatId }: ChatTimelineProps) {
 
   const sendMutation = useMutation({
     mutationFn: (content: string) =>
-      sendMessage(chatId!, content, YOUTUBE_SYSTEM_PROMPT, selectedProviders, undefined, apiCtx),
+      sendMessage(
+        chatId!,
+        content,
+        YOUTUBE_SYSTEM_PROMPT,
+        selectedProviders,
+        undefined,
+        apiCtx,
+        selectedProviders.map((provider) => ({ provider, model: selectedModels[provider] || undefined }))
+      ),
     onSuccess: () => {
       queryClient.invalidateQueries({ queryKey: ['messages', chatId] });
     },
@@ -72,6 +80,8 @@ export function ChatTimeline({ chatId }: ChatTimelineProps) {
       <ModelSelector
         selected={selectedProviders}
         onChange={setSelectedProviders}
+        models={selectedModels}
+        onModelChange={setSelectedModel}
       />
       <div className="messages">
         {messages.map((m: Message) => (
@@ -1,3 +1,6 @@
+import { useQuery } from '@tanstack/react-query';
+import type { ModelInfo } from '@idea-engine/shared';
+import { listModels } from '../services/api';
 import './ModelSelector.css';
 
 const PROVIDERS = [
@@ -9,9 +12,23 @@ const PROVIDERS = [
 interface ModelSelectorProps {
   selected: string[];
   onChange: (providers: string[]) => void;
+  models: Record<string, string>;
+  onModelChange: (provider: string, model: string) => void;
 }
 
-export function ModelSelector({ selected, onChange }: ModelSelectorProps) {
+function modelLabel(m: ModelInfo) {
+  const price =
+    m.input_usd_per_mtok !== undefined && m.output_usd_per_mtok !== undefined
+      ? ` ($${m.input_usd_per_mtok}/$${m.output_usd_per_mtok} per 1M)`
+      : '';
+  const status = m.status === 'deprecated' ? ' - deprecated' : '';
+  return `${m.model}${price}${status}`;
+}
+
+export function ModelSelector({ selected, onChange, models, onModelChange }: ModelSelectorProps) {
+  // Empty outside the desktop app; providers then run their default models
+  const { data: catalog = [] } = useQuery({ queryKey: ['models'], queryFn: () => listModels() });
+
   const toggle = (id: string) => {
     if (selected.includes(id)) {
       onChange(selected.filter((p) => p !== id));
@@ -23,16 +40,33 @@ export function ModelSelector({ selected, onChange }: ModelSelectorProps) {
   return (
     <div className="model-selector">
       <span className="selector-label">Models:</span>
-      {PROVIDERS.map((p) => (
-        <label key={p.id} className="provider-checkbox">
-          <input
-            type="checkbox"
-            checked={selected.includes(p.id)}
-            onChange={() => toggle(p.id)}
-          />
-          {p.label}
-        </label>
-      ))}
+      {PROVIDERS.map((p) => {
+        const options = catalog.filter((m) => m.provider === p.id && m.status !== 'retired');
+        return (
+          <label key={p.id} className="provider-checkbox">
+            <input
+              type="checkbox"
+              checked={selected.includes(p.id)}
+              onChange={() => toggle(p.id)}
+            />
+            {p.label}
+            {options.length > 0 && selected.includes(p.id) && (
+              <select
+                className="model-select"
+                value={models[p.id] ?? ''}
+                onChange={(e) => onModelChange(p.id, e.target.value)}
+              >
+                <option value="">Default</option>
+                {options.map((m) => (
+                  <option key={m.model} value={m.model}>
+                    {modelLabel(m)}
+                  </option>
+                ))}
+              </select>
+            )}
+          </label>
+        );
+      })}
     </div>
   );
 }
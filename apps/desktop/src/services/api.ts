@@ -9,7 +9,24 @@ import {
   apiSendMessage,
   apiSetFeedback,
 } from '@idea-engine/shared';
-import type { IdeaBundle } from '@idea-engine/shared';
+import type {
+  AbComparison,
+  AbComparisonSummary,
+  ClarifyingQuestion,
+  HierarchicalPlan,
+  IdeaBundle,
+  IdeaRef,
+  LeaderboardEntry,
+  ModelInfo,
+  ModelRefreshReport,
+  Persona,
+  RunTarget,
+  SendMessageOptions,
+  SpendGroup,
+  SpendRow,
+  TargetOutcome,
+  Usage,
+} from '@idea-engine/shared';
 
 type ApiContext = { useCloudBackend?: boolean; apiUrl?: string; apiKeys?: Record<string, string> };
 
@@ -49,18 +66,93 @@ export async function sendMessage(
   systemPrompt: string,
   providers: string[],
   apiKeys?: Record<string, string>,
-  ctx?: ApiContext
-): Promise<{ messageId: string; content: string; ideaBundles: IdeaBundle[] }> {
+  ctx?: ApiContext,
+  targets?: RunTarget[],
+  options?: SendMessageOptions
+): Promise<{
+  messageId: string;
+  content: string;
+  ideaBundles: IdeaBundle[];
+  targets?: Record<string, TargetOutcome>;
+  usage?: Usage;
+  /** Set when the problem was decomposed */
+  plan?: HierarchicalPlan;
+}> {
   if (isTauri() && !useCloud(ctx)) {
     return invoke('send_message', {
-      input: { chatId, content, systemPrompt, providers, rubric: null },
+      input: {
+        chatId,
+        content,
+        systemPrompt,
+        providers,
+        targets: targets ?? [],
+        rubric: null,
+        decompose: options?.decompose ?? false,
+        clarifications: options?.clarifications ?? [],
+      },
     });
   }
   const keys = ctx?.apiKeys ?? apiKeys;
   if (!keys || Object.keys(keys).length === 0) {
     throw new Error('Add API keys in Settings to use the web/cloud version.');
   }
-  return apiSendMessage(chatId, content, systemPrompt, providers, keys, ctx?.apiUrl);
+  return apiSendMessage(chatId, content, systemPrompt, providers, keys, ctx?.apiUrl, options);
+}
+
+/** Model catalog entries for the model selector - desktop only */
+export async function listModels(provider?: string): Promise<ModelInfo[]> {
+  if (!isTauri()) return [];
+  return invoke<ModelInfo[]>('list_models', { provider: provider ?? null });
+}
+
+/** Pull a provider's model list into the local catalog - desktop only */
+export async function refreshModels(provider: string): Promise<ModelRefreshReport> {
+  return invoke<ModelRefreshReport>('refresh_models', { provider });
+}
+
+/** Built-in personas for ensemble runs - desktop only */
+export async function listPersonas(): Promise<Persona[]> {
+  if (!isTauri()) return [];
+  return invoke<Persona[]>('list_personas');
+}
+
+/**
+ * Questions about details the problem leaves out - desktop only. Without a provider only the local
+ * heuristic runs; with one its model is asked, falling back to the heuristic on error. The model
+ * call's spend is recorded against `chatId` when given.
+ */
+export async function clarifyProblem(
+  content: string,
+  provider?: string,
+  model?: string,
+  chatId?: string
+): Promise<{ questions: ClarifyingQuestion[]; source: 'model' | 'heuristic'; error?: string; usage?: Usage }> {
+  if (!isTauri()) return { questions: [], source: 'heuristic' };
+  return invoke('clarify_problem', {
+    input: { content, provider: provider ?? null, model: model ?? null, chatId: chatId ?? null },
+  });
+}
+
+/** Expand one idea into a sub-plan stored as its child - desktop only */
+export async function drillDown(
+  target: IdeaRef,
+  systemPrompt: string,
+  providers: string[],
+  targets?: RunTarget[]
+): Promise<{
+  messageId: string;
+  content: string;
+  ideaBundles: IdeaBundle[];
+  targets: Record<string, TargetOutcome>;
+  usage: Usage;
+  parent: IdeaRef;
+}> {
+  if (!isTauri()) {
+    throw new Error('Drill-down is only available in the desktop app.');
+  }
+  return invoke('drill_down', {
+    input: { ...target, systemPrompt, providers, targets: targets ?? [], rubric: null },
+  });
 }
 
 export async function setFeedback(messageId: string, feedback: string, ctx?: ApiContext) {
@@ -69,3 +161,24 @@ export async function setFeedback(messageId: string, feedback: string, ctx?: Api
   }
   return apiSetFeedback(messageId, feedback, ctx?.apiUrl);
 }
+
+/** Provider spend summed per day, provider, model, recipe or chat - desktop only */
+export async function getSpend(groupBy: SpendGroup, since?: number): Promise<SpendRow[]> {
+  if (!isTauri()) return [];
+  return invoke<SpendRow[]>('get_spend', { input: { groupBy, since: since ?? null } });
+}
+
+/** Stored eval A/B comparisons - desktop only, the eval runner writes to the local database */
+export async function listEvalComparisons(): Promise<AbComparisonSummary[]> {
+  if (!isTauri()) return [];
+  return invoke<AbComparisonSummary[]>('list_eval_comparisons');
+}
+
+export async function getEvalComparison(id: string): Promise<AbComparison> {
+  return invoke<AbComparison>('get_eval_comparison', { id });
+}
+
+export async function getEvalLeaderboard(): Promise<LeaderboardEntry[]> {
+  if (!isTauri()) return [];
+  return invoke<LeaderboardEntry[]>('get_eval_leaderboard');
+}
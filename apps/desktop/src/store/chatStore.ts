@@ -8,6 +8,9 @@ interface ChatState {
   setCurrentChatId: (id: string | null) => void;
   selectedProviders: string[];
   setSelectedProviders: (providers: string[]) => void;
+  /** Model per provider; a provider without one runs its default model */
+  selectedModels: Record<string, string>;
+  setSelectedModel: (provider: string, model: string) => void;
   selectedTemplate: string | null;
   setSelectedTemplate: (id: string | null) => void;
   apiKeys: Record<string, string>;
@@ -25,6 +28,9 @@ export const useChatStore = create<ChatState>()(
       setCurrentChatId: (id) => set({ currentChatId: id }),
       selectedProviders: ['openai', 'anthropic', 'gemini'],
       setSelectedProviders: (providers) => set({ selectedProviders: providers }),
+      selectedModels: {},
+      setSelectedModel: (provider, model) =>
+        set((s) => ({ selectedModels: { ...s.selectedModels, [provider]: model } })),
       selectedTemplate: null,
       setSelectedTemplate: (id) => set({ selectedTemplate: id }),
       apiKeys: {},
@@ -1,13 +1,24 @@
-use super::{to_idea_bundle, AdapterError, ProviderAdapter};
-use crate::schema::{AIResponse, IdeaBundle};
+use super::cassette::post_json;
+use super::{
+    check_range, effective_params, parse_bundle, supported_format, system_with_schema, with_raised_limit, AdapterError,
+    ProviderAdapter, DEFAULT_MAX_TOKENS,
+};
+use crate::request::{CompletionRequest, ResponseFormat};
+use crate::response_schema::{tool_input_schema, RESPONSE_SCHEMA_NAME};
+use crate::schema::IdeaBundle;
+use crate::usage;
 use async_trait::async_trait;
 use serde_json::Value;
+use std::time::Instant;
 
+const BASE_URL: &str = "https://api.anthropic.com";
 const MODEL: &str = "claude-3-5-haiku-20241022";
 
 pub struct AnthropicAdapter {
     api_key: Option<String>,
     model: String,
+    base_url: String,
+    max_tokens: u32,
 }
 
 impl AnthropicAdapter {
@@ -15,6 +26,8 @@ impl AnthropicAdapter {
         Self {
             api_key,
             model: MODEL.to_string(),
+            base_url: BASE_URL.to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
         }
     }
 
@@ -22,6 +35,18 @@ impl AnthropicAdapter {
         self.model = model.to_string();
         self
     }
+
+    /// Point the adapter at another host, e.g. a proxy or the local mock provider
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Starting output limit; a truncated response is retried with a higher one
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
 }
 
 #[async_trait]
@@ -34,70 +59,131 @@ impl ProviderAdapter for AnthropicAdapter {
         &self.model
     }
 
-    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<IdeaBundle, AdapterError> {
-        let api_key = self
-            .api_key
-            .as_deref()
-            .ok_or(AdapterError::MissingApiKey)?;
+    /// With the schema format, forces a call to the response tool so its arguments are the bundle.
+    /// A reply without a `tool_use` block falls back to parsing its text.
+    async fn complete(&self, request: &CompletionRequest) -> Result<IdeaBundle, AdapterError> {
+        let format = supported_format(self.provider_name(), &self.model, request.response_format);
+        let mut body = match format {
+            ResponseFormat::Schema => {
+                let mut body = self.request_body(request, &request.system_prompt)?;
+                body["tools"] = serde_json::json!([{
+                    "name": RESPONSE_SCHEMA_NAME,
+                    "description": "Return the ideas, step plan, risks, dependencies, effort estimate and next actions.",
+                    "input_schema": tool_input_schema()
+                }]);
+                body["tool_choice"] = serde_json::json!({ "type": "tool", "name": RESPONSE_SCHEMA_NAME });
+                body
+            }
+            // No JSON mode; both fall back to the schema in the system prompt
+            ResponseFormat::Json | ResponseFormat::Text => {
+                self.request_body(request, &system_with_schema(&request.system_prompt))?
+            }
+        };
+        let (response, limit) = with_raised_limit(self.provider_name(), &self.model, &request.params, self.max_tokens, |limit| {
+            body["max_tokens"] = limit.into();
+            self.message(body.clone())
+        })
+        .await?;
 
-        let system_with_schema = format!(
-            "{}\n\nRespond with valid JSON matching this schema: ideas (array of {{title, description, rationale?}}), step_plan (array of {{order, action, details?}}), risks (array of {{description, severity, mitigation?}}), dependencies (array of strings), effort ({{time, cost?, complexity?}}), next_actions (array of {{action, priority}}).",
-            system_prompt
-        );
+        let content = match tool_input(&response) {
+            Some(input) => input.to_string(),
+            None => text_blocks(&response).ok_or_else(|| AdapterError::Parse("No tool_use or text block in response".into()))?,
+        };
+        let params = effective_params(&request.params, limit);
+        parse_bundle(self.provider_name(), &self.model, &content, params)
+    }
+
+    async fn complete_text(&self, request: &CompletionRequest) -> Result<String, AdapterError> {
+        let mut body = self.request_body(request, &request.system_prompt)?;
+        let (response, _) = with_raised_limit(self.provider_name(), &self.model, &request.params, self.max_tokens, |limit| {
+            body["max_tokens"] = limit.into();
+            self.message(body.clone())
+        })
+        .await?;
+        text_blocks(&response).ok_or_else(|| AdapterError::Parse("No text block in response".into()))
+    }
+}
 
-        let client = reqwest::Client::new();
-        let body = serde_json::json!({
+impl AnthropicAdapter {
+    /// Messages API body for the request, without `max_tokens`. Rejects `seed`, which Anthropic does
+    /// not support, and temperatures above 1.
+    fn request_body(&self, request: &CompletionRequest, system_prompt: &str) -> Result<Value, AdapterError> {
+        let params = &request.params;
+        if params.seed.is_some() {
+            return Err(AdapterError::Unsupported("anthropic does not support seed".into()));
+        }
+        check_range(self.provider_name(), "temperature", params.temperature, 0.0..=1.0)?;
+        check_range(self.provider_name(), "top_p", params.top_p, 0.0..=1.0)?;
+
+        let messages: Vec<Value> = request
+            .messages
+            .iter()
+            .map(|m| serde_json::json!({ "role": m.role, "content": m.content }))
+            .collect();
+        let mut body = serde_json::json!({
             "model": self.model,
-            "max_tokens": 4096,
-            "system": system_with_schema,
-            "messages": [
-                { "role": "user", "content": user_prompt }
-            ],
-            "temperature": 0.7
+            "system": system_prompt,
+            "messages": messages,
+            "temperature": params.temperature()
         });
-
-        let res = client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| AdapterError::Api(e.to_string()))?;
-
-        let status = res.status();
-        let text = res.text().await.map_err(|e| AdapterError::Api(e.to_string()))?;
-
-        if !status.is_success() {
-            return Err(AdapterError::Api(format!("{}: {}", status, text)));
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = top_p.into();
         }
+        if !params.stop.is_empty() {
+            body["stop_sequences"] = params.stop.clone().into();
+        }
+        Ok(body)
+    }
+
+    /// One Messages API call; returns the response JSON once `stop_reason` says it is complete
+    async fn message(&self, body: Value) -> Result<Value, AdapterError> {
+        let max_tokens = body["max_tokens"].as_u64().unwrap_or_default();
+
+        let started = Instant::now();
+        let text = post_json(self.provider_name(), &self.model, self.api_key.as_deref(), &body, |api_key| {
+            reqwest::Client::new()
+                .post(format!("{}/v1/messages", self.base_url))
+                .header("x-api-key", api_key)
+                .header("anthropic-version", "2023-06-01")
+                .header("Content-Type", "application/json")
+        })
+        .await?;
 
         let json: Value = serde_json::from_str(&text).map_err(|e| AdapterError::Parse(e.to_string()))?;
-        let content = json["content"]
-            .as_array()
-            .and_then(|arr| arr.first())
-            .and_then(|c| c["text"].as_str())
-            .ok_or_else(|| AdapterError::Parse("Missing content".into()))?;
-
-        let response = parse_json_response(content)?;
-        Ok(to_idea_bundle(
+        usage::record(
             self.provider_name(),
             &self.model,
-            response,
-            Some(content.to_string()),
-        ))
+            json["usage"]["input_tokens"].as_u64().unwrap_or_default(),
+            json["usage"]["output_tokens"].as_u64().unwrap_or_default(),
+            started,
+        );
+        match json["stop_reason"].as_str() {
+            Some("max_tokens") => Err(AdapterError::Truncated(format!(
+                "stopped at the {} token limit (stop_reason max_tokens)",
+                max_tokens
+            ))),
+            Some("refusal") => Err(AdapterError::Blocked("model refused the request (refusal)".into())),
+            _ => Ok(json),
+        }
     }
 }
 
-fn parse_json_response(content: &str) -> Result<AIResponse, AdapterError> {
-    let trimmed = content.trim();
-    let json_str = if trimmed.starts_with("```json") {
-        trimmed.trim_start_matches("```json").trim_end_matches("```").trim()
-    } else if trimmed.starts_with("```") {
-        trimmed.trim_start_matches("```").trim_end_matches("```").trim()
-    } else {
-        trimmed
-    };
-    serde_json::from_str(json_str).map_err(|e| AdapterError::Parse(format!("{}: {}", e, json_str)))
+/// Arguments of the response tool call, if the reply has one
+fn tool_input(response: &Value) -> Option<&Value> {
+    response["content"]
+        .as_array()?
+        .iter()
+        .find(|block| block["type"] == "tool_use" && block["name"] == RESPONSE_SCHEMA_NAME)
+        .map(|block| &block["input"])
+}
+
+/// All text blocks joined in order; None if there are none
+fn text_blocks(response: &Value) -> Option<String> {
+    let texts: Vec<&str> = response["content"]
+        .as_array()?
+        .iter()
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+    (!texts.is_empty()).then(|| texts.join(""))
 }
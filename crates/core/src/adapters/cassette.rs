@@ -0,0 +1,261 @@
+//! Record/replay cassettes - save the HTTP exchanges of provider calls to a file, serve them offline by request hash
+
+use super::AdapterError;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Record every live call into this cassette file
+pub const RECORD_ENV: &str = "IDEA_ENGINE_RECORD";
+/// Serve calls from this cassette file instead of the network. Only honoured with the `replay`
+/// feature, which the desktop app builds without.
+pub const REPLAY_ENV: &str = "IDEA_ENGINE_REPLAY";
+
+/// Version 1 stored parsed bundles and cannot be replayed through the adapters
+const CASSETTE_VERSION: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub enum CassetteMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl CassetteMode {
+    /// Replay wins if both variables are set, so a stray record path never reaches the network
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.trim().is_empty());
+        let replay = if cfg!(feature = "replay") { var(REPLAY_ENV) } else { None };
+        match (replay, var(RECORD_ENV)) {
+            (Some(path), _) => Some(Self::Replay(PathBuf::from(path))),
+            (None, Some(path)) => Some(Self::Record(PathBuf::from(path))),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Everything that determines a provider's answer. Credentials travel in headers and are not stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CassetteRequest {
+    pub provider: String,
+    pub model: String,
+    /// The JSON body as sent: prompts, parameters, output limit and response format
+    pub body: serde_json::Value,
+}
+
+impl CassetteRequest {
+    /// Stable across builds and platforms: FNV-1a over the request's JSON
+    pub fn hash(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in json.bytes() {
+            h ^= byte as u64;
+            h = h.wrapping_mul(0x0100_0000_01b3);
+        }
+        format!("{:016x}", h)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CassetteResponse {
+    /// Status and raw body; replay hands them to the adapter, which parses them like a live reply
+    Http {
+        status: u16,
+        /// Retry-After in seconds, when sent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after: Option<u64>,
+        body: String,
+    },
+    /// No complete response, e.g. connection refused or the body was cut off
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub hash: String,
+    pub request: CassetteRequest,
+    pub response: CassetteResponse,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+/// A cassette file shared by every adapter in the process that points at the same path
+pub struct Cassette {
+    path: PathBuf,
+    inner: Mutex<CassetteState>,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    /// Next interaction to serve per request hash, so repeated identical requests replay in order
+    cursors: HashMap<String, usize>,
+}
+
+impl Cassette {
+    /// Open (or start) the cassette at `path`. Opening the same path twice returns the same instance.
+    pub fn open(path: impl AsRef<Path>) -> Result<Arc<Self>, String> {
+        static OPEN: OnceLock<Mutex<HashMap<PathBuf, Arc<Cassette>>>> = OnceLock::new();
+        let path = path.as_ref().to_path_buf();
+        let mut open = OPEN.get_or_init(Default::default).lock().unwrap();
+        if let Some(cassette) = open.get(&path) {
+            return Ok(Arc::clone(cassette));
+        }
+        let interactions = if path.exists() {
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let file: CassetteFile = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if file.version != CASSETTE_VERSION {
+                return Err(format!(
+                    "{}: unsupported cassette version {}, record it again",
+                    path.display(),
+                    file.version
+                ));
+            }
+            file.interactions
+        } else {
+            Vec::new()
+        };
+        let cassette = Arc::new(Self {
+            path: path.clone(),
+            inner: Mutex::new(CassetteState {
+                interactions,
+                cursors: HashMap::new(),
+            }),
+        });
+        open.insert(path, Arc::clone(&cassette));
+        Ok(cassette)
+    }
+
+    /// Distinct (provider, model) pairs on the cassette, in recording order
+    pub fn targets(&self) -> Vec<(String, String)> {
+        let state = self.inner.lock().unwrap();
+        let mut targets: Vec<(String, String)> = Vec::new();
+        for i in &state.interactions {
+            let target = (i.request.provider.clone(), i.request.model.clone());
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        targets
+    }
+
+    /// Append an interaction and rewrite the file, so an interrupted run keeps what it recorded
+    pub fn record(&self, request: CassetteRequest, response: CassetteResponse) -> Result<(), String> {
+        let mut state = self.inner.lock().unwrap();
+        state.interactions.push(Interaction {
+            hash: request.hash(),
+            request,
+            response,
+        });
+        let file = CassetteFile {
+            version: CASSETTE_VERSION,
+            interactions: state.interactions.clone(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    /// The next recorded response for this request. Once every recording of a request has been
+    /// served, the last one repeats.
+    pub fn replay(&self, request: &CassetteRequest) -> Option<CassetteResponse> {
+        let hash = request.hash();
+        let mut state = self.inner.lock().unwrap();
+        let matches: Vec<usize> = state
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.hash == hash)
+            .map(|(n, _)| n)
+            .collect();
+        let cursor = state.cursors.entry(hash).or_insert(0);
+        let index = *matches.get(*cursor).or(matches.last())?;
+        *cursor += 1;
+        Some(state.interactions[index].response.clone())
+    }
+}
+
+/// POST `body` to a provider and return the raw text of a successful response; an error status
+/// becomes `AdapterError::RateLimited` (429) or `AdapterError::Api`. `build` gets the API key and
+/// returns the request with URL and headers. Under a cassette the exchange is recorded, or served from
+/// the file without a key or network; either way the caller parses the body as usual.
+pub(super) async fn post_json(
+    provider: &str,
+    model: &str,
+    api_key: Option<&str>,
+    body: &serde_json::Value,
+    build: impl FnOnce(&str) -> RequestBuilder,
+) -> Result<String, AdapterError> {
+    let mode = CassetteMode::from_env();
+    let request = CassetteRequest {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        body: body.clone(),
+    };
+    if let Some(CassetteMode::Replay(path)) = &mode {
+        let cassette = Cassette::open(path).map_err(AdapterError::Api)?;
+        let response = cassette.replay(&request).ok_or_else(|| {
+            AdapterError::Api(format!(
+                "No cassette entry for {}:{} request {}",
+                provider,
+                model,
+                request.hash()
+            ))
+        })?;
+        return reply(response);
+    }
+
+    let api_key = api_key.ok_or(AdapterError::MissingApiKey)?;
+    let response = match send(build(api_key).json(body)).await {
+        Ok(response) => response,
+        Err(message) => CassetteResponse::Error { message },
+    };
+    if let Some(CassetteMode::Record(path)) = mode {
+        Cassette::open(path)
+            .and_then(|cassette| cassette.record(request, response.clone()))
+            .map_err(AdapterError::Api)?;
+    }
+    reply(response)
+}
+
+/// Errors without the URL, which may name a private host
+async fn send(request: RequestBuilder) -> Result<CassetteResponse, String> {
+    let res = request.send().await.map_err(|e| e.without_url().to_string())?;
+    let status = res.status().as_u16();
+    // Only the delay-seconds form; an HTTP date falls back to the caller's backoff
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    let body = res.text().await.map_err(|e| e.without_url().to_string())?;
+    Ok(CassetteResponse::Http {
+        status,
+        retry_after,
+        body,
+    })
+}
+
+fn reply(response: CassetteResponse) -> Result<String, AdapterError> {
+    match response {
+        CassetteResponse::Http { status, retry_after, body } => {
+            let status = StatusCode::from_u16(status).map_err(|e| AdapterError::Api(e.to_string()))?;
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                Err(AdapterError::RateLimited {
+                    message: format!("{}: {}", status, body),
+                    retry_after,
+                })
+            } else if !status.is_success() {
+                Err(AdapterError::Api(format!("{}: {}", status, body)))
+            } else {
+                Ok(body)
+            }
+        }
+        CassetteResponse::Error { message } => Err(AdapterError::Api(message)),
+    }
+}
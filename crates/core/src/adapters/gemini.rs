@@ -1,13 +1,24 @@
-use super::{to_idea_bundle, AdapterError, ProviderAdapter};
-use crate::schema::{AIResponse, IdeaBundle};
+use super::cassette::post_json;
+use super::{
+    check_range, check_stop, effective_params, parse_bundle, supported_format, system_with_schema, with_raised_limit,
+    AdapterError, ProviderAdapter, DEFAULT_MAX_TOKENS,
+};
+use crate::request::{CompletionRequest, ResponseFormat, Role};
+use crate::response_schema::gemini_schema;
+use crate::schema::IdeaBundle;
+use crate::usage;
 use async_trait::async_trait;
 use serde_json::Value;
+use std::time::Instant;
 
-const MODEL: &str = "gemini-1.5-flash";
+const BASE_URL: &str = "https://generativelanguage.googleapis.com";
+const MODEL: &str = "gemini-2.0-flash";
 
 pub struct GeminiAdapter {
     api_key: Option<String>,
     model: String,
+    base_url: String,
+    max_tokens: u32,
 }
 
 impl GeminiAdapter {
@@ -15,6 +26,8 @@ impl GeminiAdapter {
         Self {
             api_key,
             model: MODEL.to_string(),
+            base_url: BASE_URL.to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
         }
     }
 
@@ -22,6 +35,18 @@ impl GeminiAdapter {
         self.model = model.to_string();
         self
     }
+
+    /// Point the adapter at another host, e.g. a proxy or the local mock provider
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Starting output limit; a truncated response is retried with a higher one
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
 }
 
 #[async_trait]
@@ -34,74 +59,163 @@ impl ProviderAdapter for GeminiAdapter {
         &self.model
     }
 
-    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<IdeaBundle, AdapterError> {
-        let api_key = self
-            .api_key
-            .as_deref()
-            .ok_or(AdapterError::MissingApiKey)?;
-
-        let system_with_schema = format!(
-            "{}\n\nRespond with valid JSON only. Schema: ideas (array of {{title, description, rationale?}}), step_plan (array of {{order, action, details?}}), risks (array of {{description, severity, mitigation?}}), dependencies (array of strings), effort ({{time, cost?, complexity?}}), next_actions (array of {{action, priority}}).",
-            system_prompt
-        );
+    async fn complete(&self, request: &CompletionRequest) -> Result<IdeaBundle, AdapterError> {
+        let format = supported_format(self.provider_name(), &self.model, request.response_format);
+        let (system_prompt, mime_type, schema) = match format {
+            ResponseFormat::Schema => (request.system_prompt.clone(), Some("application/json"), Some(gemini_schema())),
+            ResponseFormat::Json => (system_with_schema(&request.system_prompt), Some("application/json"), None),
+            ResponseFormat::Text => (system_with_schema(&request.system_prompt), None, None),
+        };
+        let mut body = self.request_body(request, &system_prompt)?;
+        if let Some(mime_type) = mime_type {
+            body["generationConfig"]["responseMimeType"] = mime_type.into();
+        }
+        if let Some(schema) = schema {
+            body["generationConfig"]["responseSchema"] = schema;
+        }
+        let (content, limit) = with_raised_limit(self.provider_name(), &self.model, &request.params, self.max_tokens, |limit| {
+            body["generationConfig"]["maxOutputTokens"] = limit.into();
+            self.generate(body.clone())
+        })
+        .await?;
+
+        let params = effective_params(&request.params, limit);
+        parse_bundle(self.provider_name(), &self.model, &content, params)
+    }
 
-        let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            self.model, api_key
-        );
+    async fn complete_text(&self, request: &CompletionRequest) -> Result<String, AdapterError> {
+        let mut body = self.request_body(request, &request.system_prompt)?;
+        with_raised_limit(self.provider_name(), &self.model, &request.params, self.max_tokens, |limit| {
+            body["generationConfig"]["maxOutputTokens"] = limit.into();
+            self.generate(body.clone())
+        })
+        .await
+        .map(|(text, _)| text)
+    }
+}
 
-        let body = serde_json::json!({
-            "contents": [{
-                "parts": [
-                    { "text": format!("{}\n\n---\n\n{}", system_with_schema, user_prompt) }
-                ]
-            }],
+impl GeminiAdapter {
+    /// generateContent body for the request, without `maxOutputTokens`
+    fn request_body(&self, request: &CompletionRequest, system_prompt: &str) -> Result<Value, AdapterError> {
+        let params = &request.params;
+        check_range(self.provider_name(), "temperature", params.temperature, 0.0..=2.0)?;
+        check_range(self.provider_name(), "top_p", params.top_p, 0.0..=1.0)?;
+        check_stop(self.provider_name(), params, 5)?;
+
+        let contents: Vec<Value> = request
+            .messages
+            .iter()
+            .map(|m| {
+                let role = match m.role {
+                    Role::User => "user",
+                    Role::Assistant => "model",
+                };
+                serde_json::json!({ "role": role, "parts": [{ "text": m.content }] })
+            })
+            .collect();
+        let mut body = serde_json::json!({
+            "systemInstruction": {
+                "parts": [{ "text": system_prompt }]
+            },
+            "contents": contents,
             "generationConfig": {
-                "temperature": 0.7,
-                "maxOutputTokens": 4096,
-                "responseMimeType": "application/json"
+                "temperature": params.temperature()
             }
         });
-
-        let client = reqwest::Client::new();
-        let res = client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| AdapterError::Api(e.to_string()))?;
-
-        let status = res.status();
-        let text = res.text().await.map_err(|e| AdapterError::Api(e.to_string()))?;
-
-        if !status.is_success() {
-            return Err(AdapterError::Api(format!("{}: {}", status, text)));
+        if let Some(seed) = params.seed {
+            body["generationConfig"]["seed"] = seed.into();
+        }
+        if let Some(top_p) = params.top_p {
+            body["generationConfig"]["topP"] = top_p.into();
+        }
+        if !params.stop.is_empty() {
+            body["generationConfig"]["stopSequences"] = params.stop.clone().into();
         }
+        Ok(body)
+    }
 
-        let json: Value = serde_json::from_str(&text).map_err(|e| AdapterError::Parse(e.to_string()))?;
-        let content = json["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .ok_or_else(|| AdapterError::Parse("Missing content".into()))?;
+    /// One generateContent call; returns the first candidate's text parts joined
+    async fn generate(&self, body: Value) -> Result<String, AdapterError> {
+        let url = format!("{}/v1beta/models/{}:generateContent", self.base_url, self.model);
+        let max_tokens = body["generationConfig"]["maxOutputTokens"].as_u64().unwrap_or_default();
+
+        // Key in a header, never the URL, so it cannot end up in error messages
+        let started = Instant::now();
+        let text = post_json(self.provider_name(), &self.model, self.api_key.as_deref(), &body, |api_key| {
+            reqwest::Client::new()
+                .post(&url)
+                .header("x-goog-api-key", api_key)
+                .header("Content-Type", "application/json")
+        })
+        .await?;
 
-        let response = parse_json_response(content)?;
-        Ok(to_idea_bundle(
+        let json: Value = serde_json::from_str(&text).map_err(|e| AdapterError::Parse(e.to_string()))?;
+        // Thinking tokens are billed as output
+        let tokens = |key: &str| json["usageMetadata"][key].as_u64().unwrap_or_default();
+        usage::record(
             self.provider_name(),
             &self.model,
-            response,
-            Some(content.to_string()),
-        ))
+            tokens("promptTokenCount"),
+            tokens("candidatesTokenCount") + tokens("thoughtsTokenCount"),
+            started,
+        );
+        check_feedback(&json, max_tokens)?;
+        let parts = json["candidates"][0]["content"]["parts"]
+            .as_array()
+            .ok_or_else(|| AdapterError::Parse("Missing content".into()))?;
+        Ok(parts.iter().filter_map(|p| p["text"].as_str()).collect())
     }
 }
 
-fn parse_json_response(content: &str) -> Result<AIResponse, AdapterError> {
-    let trimmed = content.trim();
-    let json_str = if trimmed.starts_with("```json") {
-        trimmed.trim_start_matches("```json").trim_end_matches("```").trim()
-    } else if trimmed.starts_with("```") {
-        trimmed.trim_start_matches("```").trim_end_matches("```").trim()
+/// Turn a blocked prompt or a candidate that did not finish normally into a specific error
+fn check_feedback(response: &Value, max_tokens: u64) -> Result<(), AdapterError> {
+    if let Some(reason) = response["promptFeedback"]["blockReason"].as_str() {
+        return Err(AdapterError::Blocked(format!(
+            "prompt rejected before generation ({}){}",
+            reason,
+            flagged_categories(&response["promptFeedback"]["safetyRatings"])
+        )));
+    }
+    let candidate = &response["candidates"][0];
+    match candidate["finishReason"].as_str() {
+        None | Some("STOP") | Some("FINISH_REASON_UNSPECIFIED") => Ok(()),
+        Some("MAX_TOKENS") => Err(AdapterError::Truncated(format!(
+            "stopped at the {} token limit (finishReason MAX_TOKENS)",
+            max_tokens
+        ))),
+        Some("SAFETY") => Err(AdapterError::Blocked(format!(
+            "response withheld by safety filters{}",
+            flagged_categories(&candidate["safetyRatings"])
+        ))),
+        Some("RECITATION") => Err(AdapterError::Blocked(
+            "response withheld because it recited source material too closely (RECITATION)".into(),
+        )),
+        Some(other @ ("BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY")) => Err(AdapterError::Blocked(
+            format!("response withheld by content policy ({})", other),
+        )),
+        Some(other) => Err(AdapterError::Api(format!("Generation stopped early: {}", other))),
+    }
+}
+
+/// ": HARM_CATEGORY_X (HIGH), ..." for ratings flagged as blocked or above LOW
+fn flagged_categories(ratings: &Value) -> String {
+    let flagged: Vec<String> = ratings
+        .as_array()
+        .map(|r| r.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|r| r["blocked"] == true || matches!(r["probability"].as_str(), Some("MEDIUM" | "HIGH")))
+        .map(|r| {
+            format!(
+                "{} ({})",
+                r["category"].as_str().unwrap_or("unknown"),
+                r["probability"].as_str().unwrap_or("blocked")
+            )
+        })
+        .collect();
+    if flagged.is_empty() {
+        String::new()
     } else {
-        trimmed
-    };
-    serde_json::from_str(json_str).map_err(|e| AdapterError::Parse(format!("{}: {}", e, json_str)))
+        format!(": {}", flagged.join(", "))
+    }
 }
@@ -1,6 +1,7 @@
 //! Provider adapters - map normalized prompts to each provider format
 
 mod anthropic;
+pub mod cassette;
 mod gemini;
 mod openai;
 
@@ -8,15 +9,60 @@ pub use anthropic::AnthropicAdapter;
 pub use gemini::GeminiAdapter;
 pub use openai::OpenAIAdapter;
 
+use self::cassette::{Cassette, CassetteMode};
+use crate::catalog;
+use crate::json_repair::parse_lenient;
+use crate::normalize::normalize_response;
+use crate::request::{CompletionRequest, GenerationParams, ResponseFormat};
+use crate::response_schema::tool_input_schema;
 use crate::schema::{AIResponse, IdeaBundle};
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
 use uuid::Uuid;
 
+pub const PROVIDERS: [&str; 3] = ["openai", "anthropic", "gemini"];
+
 #[async_trait]
 pub trait ProviderAdapter: Send + Sync {
     fn provider_name(&self) -> &'static str;
     fn model_name(&self) -> &str;
-    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<IdeaBundle, AdapterError>;
+    async fn complete(&self, request: &CompletionRequest) -> Result<IdeaBundle, AdapterError>;
+    /// Free-form completion without the bundle schema, for judges and other helper calls.
+    /// `request.response_format` is ignored.
+    async fn complete_text(&self, request: &CompletionRequest) -> Result<String, AdapterError>;
+}
+
+/// "provider:model" for an adapter, matching `IdeaBundle::target`
+pub fn target_label(adapter: &dyn ProviderAdapter) -> String {
+    format!("{}:{}", adapter.provider_name(), adapter.model_name())
+}
+
+/// One provider and model to run, with parameters that override the request's for this target only
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    pub provider: String,
+    /// Provider default when unset
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub params: Option<GenerationParams>,
+}
+
+impl Target {
+    pub fn new(provider: &str, model: Option<&str>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.map(String::from),
+            params: None,
+        }
+    }
+
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = Some(params);
+        self
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +73,229 @@ pub enum AdapterError {
     Parse(String),
     #[error("Missing API key")]
     MissingApiKey,
+    /// The provider refused to answer - safety filters, recitation checks, content policy
+    #[error("Blocked: {0}")]
+    Blocked(String),
+    /// Output hit the token limit, even after retrying with the highest allowed limit
+    #[error("Response truncated: {0}")]
+    Truncated(String),
+    /// A generation parameter the provider does not accept, rejected before any request is sent
+    #[error("Unsupported option: {0}")]
+    Unsupported(String),
+    /// HTTP 429, with the provider's Retry-After in seconds when it sent one
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, retry_after: Option<u64> },
+}
+
+/// Output token limit adapters start with
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+/// Highest limit a truncated response is retried with
+pub const MAX_TOKENS_CEILING: u32 = 16384;
+
+/// Run `call` with the request's output limit. Without an explicit `max_tokens`, a truncated reply is
+/// retried with double the limit up to the ceiling; an explicit one is respected as a hard cap. Both
+/// stay within the model's catalog output limit, and retired models are rejected before any call.
+/// Returns the result with the limit of the call that produced it.
+async fn with_raised_limit<T, F, Fut>(
+    provider: &str,
+    model: &str,
+    params: &GenerationParams,
+    default_limit: u32,
+    mut call: F,
+) -> Result<(T, u32), AdapterError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, AdapterError>>,
+{
+    let info = catalog::lookup(provider, model);
+    if let Some(info) = info.as_ref().filter(|i| i.status == catalog::ModelStatus::Retired) {
+        return Err(AdapterError::Unsupported(info.deprecation_warning().unwrap_or_default()));
+    }
+    let model_max = info.and_then(|i| i.max_output_tokens);
+    if let (Some(requested), Some(max)) = (params.max_tokens, model_max) {
+        if requested > max {
+            return Err(AdapterError::Unsupported(format!(
+                "{}:{} max_tokens is at most {}, got {}",
+                provider, model, max, requested
+            )));
+        }
+    }
+    let mut limit = params.max_tokens.unwrap_or_else(|| output_limit(default_limit, model_max));
+    let ceiling = if params.max_tokens.is_some() {
+        limit
+    } else {
+        output_limit(MAX_TOKENS_CEILING, model_max).max(limit)
+    };
+    loop {
+        match call(limit).await {
+            Err(AdapterError::Truncated(_)) if limit < ceiling => {
+                limit = limit.saturating_mul(2).min(ceiling);
+            }
+            result => return result.map(|output| (output, limit)),
+        }
+    }
+}
+
+/// `limit`, lowered to the model's output limit when the catalog has one
+fn output_limit(limit: u32, model_max: Option<u32>) -> u32 {
+    model_max.map_or(limit, |max| limit.min(max))
+}
+
+/// The requested format, or JSON mode when the catalog says the model has no native structured output
+fn supported_format(provider: &str, model: &str, requested: ResponseFormat) -> ResponseFormat {
+    let structured = catalog::lookup(provider, model).and_then(|i| i.structured_output);
+    match requested {
+        ResponseFormat::Schema if structured == Some(false) => ResponseFormat::Json,
+        other => other,
+    }
+}
+
+/// Reject values outside `range` for a named parameter
+fn check_range(provider: &str, name: &str, value: Option<f64>, range: std::ops::RangeInclusive<f64>) -> Result<(), AdapterError> {
+    match value {
+        Some(v) if !range.contains(&v) => Err(AdapterError::Unsupported(format!(
+            "{} {} must be between {} and {}, got {}",
+            provider,
+            name,
+            range.start(),
+            range.end(),
+            v
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reject more stop sequences than the provider accepts
+fn check_stop(provider: &str, params: &GenerationParams, max: usize) -> Result<(), AdapterError> {
+    if params.stop.len() > max {
+        return Err(AdapterError::Unsupported(format!(
+            "{} accepts at most {} stop sequences, got {}",
+            provider,
+            max,
+            params.stop.len()
+        )));
+    }
+    Ok(())
+}
+
+/// System prompt with the response schema spelled out, for requests without native structured output
+fn system_with_schema(system_prompt: &str) -> String {
+    format!(
+        "{}\n\nRespond with a single JSON object matching this JSON Schema:\n{}",
+        system_prompt,
+        tool_input_schema()
+    )
+}
+
+/// The parameters a bundle was generated with: defaults filled in and `limit`, the output limit of
+/// the call that succeeded
+fn effective_params(params: &GenerationParams, limit: u32) -> GenerationParams {
+    GenerationParams {
+        temperature: Some(params.temperature()),
+        max_tokens: Some(limit),
+        ..params.clone()
+    }
+}
+
+/// API key for a provider: `<PROVIDER>_API_KEY` from the environment, else the keychain entry the desktop app stores
+pub fn api_key_for(provider: &str) -> Option<String> {
+    let env_var = format!("{}_API_KEY", provider.to_uppercase());
+    if let Ok(key) = std::env::var(env_var) {
+        if !key.trim().is_empty() {
+            return Some(key);
+        }
+    }
+    keyring::Entry::new(&format!("idea-engine-{}", provider), "api-key")
+        .ok()
+        .and_then(|e| e.get_password().ok())
+}
+
+/// Base URL override for a provider from `<PROVIDER>_BASE_URL`, e.g. to point at the mock provider
+pub fn base_url_for(provider: &str) -> Option<String> {
+    std::env::var(format!("{}_BASE_URL", provider.to_uppercase()))
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+}
+
+/// Build an adapter for a provider name (default model unless `model` is given), or None if the name is unknown.
+/// `<PROVIDER>_BASE_URL` overrides the API host.
+pub fn adapter_for(provider: &str, model: Option<&str>, api_key: Option<String>) -> Option<Arc<dyn ProviderAdapter>> {
+    let base_url = base_url_for(provider);
+    match provider {
+        "openai" => {
+            let mut adapter = OpenAIAdapter::new(api_key);
+            if let Some(m) = model {
+                adapter = adapter.with_model(m);
+            }
+            if let Some(url) = &base_url {
+                adapter = adapter.with_base_url(url);
+            }
+            Some(Arc::new(adapter))
+        }
+        "anthropic" => {
+            let mut adapter = AnthropicAdapter::new(api_key);
+            if let Some(m) = model {
+                adapter = adapter.with_model(m);
+            }
+            if let Some(url) = &base_url {
+                adapter = adapter.with_base_url(url);
+            }
+            Some(Arc::new(adapter))
+        }
+        "gemini" => {
+            let mut adapter = GeminiAdapter::new(api_key);
+            if let Some(m) = model {
+                adapter = adapter.with_model(m);
+            }
+            if let Some(url) = &base_url {
+                adapter = adapter.with_base_url(url);
+            }
+            Some(Arc::new(adapter))
+        }
+        _ => None,
+    }
+}
+
+/// Every provider usable right now: those with an API key, or every provider and model on the replay cassette
+pub fn available_adapters() -> Result<Vec<Arc<dyn ProviderAdapter>>, String> {
+    if let Some(CassetteMode::Replay(path)) = CassetteMode::from_env() {
+        let cassette = Cassette::open(path)?;
+        return Ok(cassette
+            .targets()
+            .iter()
+            .filter_map(|(provider, model)| adapter_for(provider, Some(model), None))
+            .collect());
+    }
+    Ok(PROVIDERS
+        .iter()
+        .filter_map(|p| api_key_for(p).and_then(|key| adapter_for(p, None, Some(key))))
+        .collect())
+}
+
+/// Adapter for one provider (default model unless `model` is given), honouring record/replay cassettes.
+/// Replay needs no API key.
+pub fn target_adapter(provider: &str, model: Option<&str>) -> Result<Arc<dyn ProviderAdapter>, String> {
+    let replaying = matches!(CassetteMode::from_env(), Some(CassetteMode::Replay(_)));
+    let key = api_key_for(provider);
+    if key.is_none() && !replaying {
+        return Err(format!("No API key for {}", provider));
+    }
+    adapter_for(provider, model, key).ok_or_else(|| format!("Unknown provider: {}", provider))
+}
+
+/// Parse model output into a bundle, tolerating prose, fences, trailing commas and truncation, then
+/// normalizing field aliases and enum values. Each repair and coercion becomes a warning on the bundle.
+/// `params` are recorded on the bundle.
+fn parse_bundle(provider: &str, model: &str, content: &str, params: GenerationParams) -> Result<IdeaBundle, AdapterError> {
+    let (value, repairs) = parse_lenient::<serde_json::Value>(content).map_err(AdapterError::Parse)?;
+    let (response, coercions) = normalize_response(value).map_err(AdapterError::Parse)?;
+    let mut bundle = to_idea_bundle(provider, model, response, Some(content.to_string()));
+    bundle.warnings = repairs.iter().map(ToString::to_string).chain(coercions).collect();
+    if let Some(warning) = catalog::lookup(provider, model).and_then(|i| i.deprecation_warning()) {
+        bundle.warnings.push(warning);
+    }
+    bundle.params = Some(params);
+    Ok(bundle)
 }
 
 fn to_idea_bundle(
@@ -52,6 +321,11 @@ fn to_idea_bundle(
         effort: response.effort,
         next_actions: response.next_actions,
         raw_response: raw,
+        warnings: Vec::new(),
+        repair_attempts: 0,
+        params: None,
+        persona: None,
+        usage: None,
         created_at,
     }
 }
@@ -1,13 +1,24 @@
-use super::{to_idea_bundle, AdapterError, ProviderAdapter};
-use crate::schema::{AIResponse, IdeaBundle};
+use super::cassette::post_json;
+use super::{
+    check_range, check_stop, effective_params, parse_bundle, supported_format, system_with_schema, with_raised_limit,
+    AdapterError, ProviderAdapter, DEFAULT_MAX_TOKENS,
+};
+use crate::request::{CompletionRequest, ResponseFormat};
+use crate::response_schema::{openai_strict_schema, RESPONSE_SCHEMA_NAME};
+use crate::schema::IdeaBundle;
+use crate::usage;
 use async_trait::async_trait;
 use serde_json::Value;
+use std::time::Instant;
 
+const BASE_URL: &str = "https://api.openai.com";
 const MODEL: &str = "gpt-4o-mini";
 
 pub struct OpenAIAdapter {
     api_key: Option<String>,
     model: String,
+    base_url: String,
+    max_tokens: u32,
 }
 
 impl OpenAIAdapter {
@@ -15,6 +26,8 @@ impl OpenAIAdapter {
         Self {
             api_key,
             model: MODEL.to_string(),
+            base_url: BASE_URL.to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
         }
     }
 
@@ -22,6 +35,18 @@ impl OpenAIAdapter {
         self.model = model.to_string();
         self
     }
+
+    /// Point the adapter at another host, e.g. a proxy or the local mock provider
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Starting output limit; a truncated response is retried with a higher one
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
 }
 
 #[async_trait]
@@ -34,131 +59,111 @@ impl ProviderAdapter for OpenAIAdapter {
         &self.model
     }
 
-    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<IdeaBundle, AdapterError> {
-        let api_key = self
-            .api_key
-            .as_deref()
-            .ok_or(AdapterError::MissingApiKey)?;
+    async fn complete(&self, request: &CompletionRequest) -> Result<IdeaBundle, AdapterError> {
+        let format = supported_format(self.provider_name(), &self.model, request.response_format);
+        let (system_prompt, response_format) = match format {
+            ResponseFormat::Schema => (
+                request.system_prompt.clone(),
+                Some(serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": RESPONSE_SCHEMA_NAME,
+                        "strict": true,
+                        "schema": openai_strict_schema()
+                    }
+                })),
+            ),
+            ResponseFormat::Json => (
+                system_with_schema(&request.system_prompt),
+                Some(serde_json::json!({ "type": "json_object" })),
+            ),
+            ResponseFormat::Text => (system_with_schema(&request.system_prompt), None),
+        };
+        let (content, limit) = with_raised_limit(self.provider_name(), &self.model, &request.params, self.max_tokens, |limit| {
+            self.chat(request, &system_prompt, response_format.clone(), limit)
+        })
+        .await?;
+        let params = effective_params(&request.params, limit);
+        parse_bundle(self.provider_name(), &self.model, &content, params)
+    }
 
-        let client = reqwest::Client::new();
-        let body = serde_json::json!({
-            "model": self.model,
-            "messages": [
-                { "role": "system", "content": system_prompt },
-                { "role": "user", "content": user_prompt }
-            ],
-            "response_format": {
-                "type": "json_schema",
-                "json_schema": {
-                    "name": "idea_response",
-                    "strict": true,
-                    "schema": openai_schema()
-                }
-            },
-            "temperature": 0.7
-        });
+    async fn complete_text(&self, request: &CompletionRequest) -> Result<String, AdapterError> {
+        with_raised_limit(self.provider_name(), &self.model, &request.params, self.max_tokens, |limit| {
+            self.chat(request, &request.system_prompt, None, limit)
+        })
+        .await
+        .map(|(text, _)| text)
+    }
+}
 
-        let res = client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| AdapterError::Api(e.to_string()))?;
+impl OpenAIAdapter {
+    /// One chat completion; returns the message content
+    async fn chat(
+        &self,
+        request: &CompletionRequest,
+        system_prompt: &str,
+        response_format: Option<Value>,
+        max_tokens: u32,
+    ) -> Result<String, AdapterError> {
+        let params = &request.params;
+        check_range(self.provider_name(), "temperature", params.temperature, 0.0..=2.0)?;
+        check_range(self.provider_name(), "top_p", params.top_p, 0.0..=1.0)?;
+        check_stop(self.provider_name(), params, 4)?;
 
-        let status = res.status();
-        let text = res.text().await.map_err(|e| AdapterError::Api(e.to_string()))?;
+        let mut messages = vec![serde_json::json!({ "role": "system", "content": system_prompt })];
+        messages.extend(request.messages.iter().map(|m| serde_json::json!({ "role": m.role, "content": m.content })));
 
-        if !status.is_success() {
-            return Err(AdapterError::Api(format!("{}: {}", status, text)));
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "temperature": params.temperature(),
+            "max_completion_tokens": max_tokens
+        });
+        if let Some(seed) = params.seed {
+            body["seed"] = seed.into();
+        }
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = top_p.into();
+        }
+        if !params.stop.is_empty() {
+            body["stop"] = params.stop.clone().into();
+        }
+        if let Some(format) = response_format {
+            body["response_format"] = format;
         }
 
-        let json: Value = serde_json::from_str(&text).map_err(|e| AdapterError::Parse(e.to_string()))?;
-        let content = json["choices"][0]["message"]["content"]
-            .as_str()
-            .ok_or_else(|| AdapterError::Parse("Missing content".into()))?;
-
-        let response: AIResponse = serde_json::from_str(content)
-            .map_err(|e| AdapterError::Parse(format!("{}: {}", e, content)))?;
+        let started = Instant::now();
+        let text = post_json(self.provider_name(), &self.model, self.api_key.as_deref(), &body, |api_key| {
+            reqwest::Client::new()
+                .post(format!("{}/v1/chat/completions", self.base_url))
+                .header("Authorization", format!("Bearer {}", api_key))
+                .header("Content-Type", "application/json")
+        })
+        .await?;
 
-        Ok(to_idea_bundle(
+        let json: Value = serde_json::from_str(&text).map_err(|e| AdapterError::Parse(e.to_string()))?;
+        usage::record(
             self.provider_name(),
             &self.model,
-            response,
-            Some(content.to_string()),
-        ))
+            json["usage"]["prompt_tokens"].as_u64().unwrap_or_default(),
+            json["usage"]["completion_tokens"].as_u64().unwrap_or_default(),
+            started,
+        );
+        if json["choices"][0]["finish_reason"] == "length" {
+            return Err(AdapterError::Truncated(format!(
+                "stopped at the {} token limit (finish_reason length)",
+                max_tokens
+            )));
+        }
+        if json["choices"][0]["finish_reason"] == "content_filter" {
+            return Err(AdapterError::Blocked("response withheld by the content filter (content_filter)".into()));
+        }
+        if let Some(refusal) = json["choices"][0]["message"]["refusal"].as_str() {
+            return Err(AdapterError::Blocked(format!("model refused: {}", refusal)));
+        }
+        json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| AdapterError::Parse("Missing content".into()))
     }
 }
-
-fn openai_schema() -> Value {
-    serde_json::json!({
-        "type": "object",
-        "properties": {
-            "ideas": {
-                "type": "array",
-                "items": {
-                    "type": "object",
-                    "properties": {
-                        "title": { "type": "string" },
-                        "description": { "type": "string" },
-                        "rationale": { "type": "string" }
-                    },
-                    "required": ["title", "description"],
-                    "additionalProperties": false
-                }
-            },
-            "step_plan": {
-                "type": "array",
-                "items": {
-                    "type": "object",
-                    "properties": {
-                        "order": { "type": "integer" },
-                        "action": { "type": "string" },
-                        "details": { "type": "string" }
-                    },
-                    "required": ["order", "action"],
-                    "additionalProperties": false
-                }
-            },
-            "risks": {
-                "type": "array",
-                "items": {
-                    "type": "object",
-                    "properties": {
-                        "description": { "type": "string" },
-                        "severity": { "type": "string", "enum": ["low", "medium", "high"] },
-                        "mitigation": { "type": "string" }
-                    },
-                    "required": ["description", "severity"],
-                    "additionalProperties": false
-                }
-            },
-            "dependencies": { "type": "array", "items": { "type": "string" } },
-            "effort": {
-                "type": "object",
-                "properties": {
-                    "time": { "type": "string" },
-                    "cost": { "type": "string" },
-                    "complexity": { "type": "string", "enum": ["low", "medium", "high"] }
-                },
-                "required": ["time"],
-                "additionalProperties": false
-            },
-            "next_actions": {
-                "type": "array",
-                "items": {
-                    "type": "object",
-                    "properties": {
-                        "action": { "type": "string" },
-                        "priority": { "type": "string", "enum": ["immediate", "short", "medium", "long"] }
-                    },
-                    "required": ["action", "priority"],
-                    "additionalProperties": false
-                }
-            }
-        },
-        "required": ["ideas", "step_plan", "risks", "dependencies", "effort", "next_actions"],
-        "additionalProperties": false
-    })
-}
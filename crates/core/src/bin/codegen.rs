@@ -0,0 +1,44 @@
+//! Codegen - writes the TypeScript mirrors of core types and the response JSON Schema into packages/shared
+//! Usage: cargo run -p idea-engine-core --bin codegen -- [--out-dir packages/shared/src/generated] [--check]
+
+use clap::Parser;
+use idea_engine_core::codegen::typescript_files;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "codegen", about = "Generate TypeScript types from the core schema")]
+struct Cli {
+    #[arg(long, default_value = "packages/shared/src/generated")]
+    out_dir: PathBuf,
+    /// Write nothing; exit with status 1 if any generated file is missing or stale
+    #[arg(long)]
+    check: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut stale = Vec::new();
+    for (name, contents) in typescript_files() {
+        let path = cli.out_dir.join(&name);
+        let current = std::fs::read_to_string(&path).ok();
+        if current.as_deref() == Some(contents.as_str()) {
+            continue;
+        }
+        if cli.check {
+            stale.push(path.display().to_string());
+            continue;
+        }
+        if let Err(e) = std::fs::create_dir_all(&cli.out_dir).and_then(|_| std::fs::write(&path, contents)) {
+            eprintln!("{}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+        println!("wrote {}", path.display());
+    }
+    if stale.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("Stale generated files (run the codegen bin): {}", stale.join(", "));
+        ExitCode::FAILURE
+    }
+}
@@ -1,56 +1,488 @@
 //! Local eval runner - CLI entry point
-//! Usage: cargo run -p idea-engine-core --bin eval -- --db path --recipe id --problems id1,id2
-
-use idea_engine_core::eval::run_eval;
-use std::env;
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut db_path = String::new();
-    let mut recipe_id = String::new();
-    let mut problem_ids: Vec<String> = Vec::new();
-
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--db" => {
-                i += 1;
-                if i < args.len() {
-                    db_path = args[i].clone();
+//! Usage: cargo run -p idea-engine-core --bin eval -- --db path <command>
+//!   dataset import|export|harvest|list    manage problem datasets
+//!   run --recipe id --dataset name        run a recipe and store the scores
+//!   compare <recipeA> <recipeB>           diff the latest runs of two recipes (or run both with --fresh)
+//!   ab <recipeA> <recipeB> --repeats N    repeated runs per arm with bootstrap CIs and paired tests
+//!   list-comparisons                      list stored A/B comparisons
+//!   tournament --entrant r[@p:m] --judge p judge pairwise matches and update Elo/Bradley-Terry ratings
+//!   leaderboard                           show the judge leaderboard
+//!   report [--run-id id | --recipe id]    render a stored run
+//!   list-runs [--recipe id]               list stored runs
+//! --record/--replay <cassette.json> capture provider calls or serve them offline (no keys, no network)
+//! Exit codes: 0 ok, 1 error, 2 gate failed (--fail-if-regression, --fail-on-assertions, --fail-if-worse)
+
+use clap::{Args, Parser, Subcommand};
+use idea_engine_core::adapters::cassette::{RECORD_ENV, REPLAY_ENV};
+use idea_engine_core::eval::dataset::{export_dataset, harvest_from_chats, import_dataset, load_dataset, DatasetFormat};
+use idea_engine_core::eval::ab::{load_comparison, run_ab, AbArm, AbOptions, Verdict};
+use idea_engine_core::eval::report::{render, render_comparison, render_leaderboard, ReportFormat};
+use idea_engine_core::eval::tournament::{leaderboard, recompute_ratings, run_tournament, TournamentOptions};
+use idea_engine_core::eval::{compare_runs, latest_run_id, load_run, run_eval, total_change_pct, EvalRun};
+use idea_engine_core::Storage;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "eval", about = "Replay eval problems against prompt recipes and report score deltas")]
+struct Cli {
+    /// SQLite database (same file the desktop app uses)
+    #[arg(long, global = true, env = "IDEA_ENGINE_DB")]
+    db: Option<PathBuf>,
+    /// Record every provider call into this cassette file
+    #[arg(long, global = true, env = "IDEA_ENGINE_RECORD", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Serve provider calls from this cassette file instead of the network
+    #[arg(long, global = true, env = "IDEA_ENGINE_REPLAY")]
+    replay: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage eval datasets
+    #[command(subcommand)]
+    Dataset(DatasetCommand),
+    /// Run a recipe over a set of problems and store the results
+    Run {
+        #[arg(long)]
+        recipe: String,
+        /// Recipe whose previous run is the delta baseline (default: the same recipe)
+        #[arg(long)]
+        baseline: Option<String>,
+        #[command(flatten)]
+        problems: ProblemArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Compare recipe B against recipe A, per problem, provider and model
+    Compare {
+        recipe_a: String,
+        recipe_b: String,
+        /// Run both recipes now instead of comparing their latest stored runs
+        #[arg(long)]
+        fresh: bool,
+        #[command(flatten)]
+        problems: ProblemArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Statistically compare two arms (recipes, or one recipe on two models) over repeated runs
+    Ab {
+        recipe_a: String,
+        recipe_b: String,
+        /// Pin arm A to `provider` or `provider:model`
+        #[arg(long)]
+        target_a: Option<String>,
+        /// Pin arm B to `provider` or `provider:model`
+        #[arg(long)]
+        target_b: Option<String>,
+        /// Runs of every problem per arm
+        #[arg(long, default_value_t = 3)]
+        repeats: u32,
+        #[arg(long, default_value_t = 0.05)]
+        alpha: f64,
+        #[arg(long, default_value_t = 2000)]
+        bootstrap: usize,
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+        /// Exit with status 2 if B is significantly worse on the total score
+        #[arg(long)]
+        fail_if_worse: bool,
+        /// Re-render a stored comparison instead of running a new one
+        #[arg(long)]
+        show: Option<String>,
+        #[command(flatten)]
+        problems: ProblemArgs,
+        /// table, md, json, junit or html
+        #[arg(long, default_value = "table")]
+        format: String,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// List stored A/B comparisons, newest first
+    ListComparisons,
+    /// Have a judge model pick between bundles of different contestants, then update the leaderboard
+    Tournament {
+        /// `recipe`, `recipe@provider` or `recipe@provider:model`; repeat for each contestant
+        #[arg(long = "entrant", required = true)]
+        entrants: Vec<String>,
+        /// Judge as `provider` or `provider:model`
+        #[arg(long)]
+        judge: String,
+        /// Elo K-factor
+        #[arg(long, default_value_t = 32.0)]
+        k_factor: f64,
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+        #[command(flatten)]
+        problems: ProblemArgs,
+        /// table, md, json, junit or html
+        #[arg(long, default_value = "table")]
+        format: String,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Show the judge leaderboard
+    Leaderboard {
+        /// Recompute ratings from every stored match with this Elo K-factor first
+        #[arg(long)]
+        recompute: Option<f64>,
+        /// table, md, json, junit or html
+        #[arg(long, default_value = "table")]
+        format: String,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Render a stored run (default: the latest run)
+    Report {
+        #[arg(long, conflicts_with = "recipe")]
+        run_id: Option<String>,
+        /// Report the latest run of this recipe
+        #[arg(long)]
+        recipe: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// List stored runs, newest first
+    ListRuns {
+        #[arg(long)]
+        recipe: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DatasetCommand {
+    /// Import problems from a JSONL or CSV file
+    Import {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        file: PathBuf,
+        /// jsonl or csv (default: from the file extension)
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Export a dataset to a JSONL or CSV file
+    Export {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        file: PathBuf,
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Copy user messages whose answers got the given feedback into a dataset
+    Harvest {
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "helpful")]
+        feedback: String,
+    },
+    /// List datasets
+    List,
+}
+
+#[derive(Args)]
+struct ProblemArgs {
+    /// Comma-separated problem IDs
+    #[arg(long, value_delimiter = ',', conflicts_with = "dataset")]
+    problems: Vec<String>,
+    /// Run every problem in this dataset
+    #[arg(long)]
+    dataset: Option<String>,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// table, md, json, junit or html
+    #[arg(long, default_value = "table")]
+    format: String,
+    /// Write the report to a file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Exit with status 2 if the total score drops by more than this percentage
+    #[arg(long, value_name = "PCT")]
+    fail_if_regression: Option<f64>,
+    /// Exit with status 2 if any assertion failed
+    #[arg(long)]
+    fail_on_assertions: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match execute(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Eval error: {}", e);
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn execute(cli: Cli) -> Result<ExitCode, String> {
+    let db = cli.db.ok_or("Pass --db <path> or set IDEA_ENGINE_DB")?;
+    // The adapters read the cassette mode from the environment
+    if let Some(path) = &cli.record {
+        std::env::set_var(RECORD_ENV, path);
+    }
+    if let Some(path) = &cli.replay {
+        std::env::set_var(REPLAY_ENV, path);
+    }
+    let storage = Storage::new(&db).map_err(|e| e.to_string())?;
+
+    match cli.command {
+        Command::Dataset(cmd) => {
+            dataset_command(&storage, cmd)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Run {
+            recipe,
+            baseline,
+            problems,
+            output,
+        } => {
+            let ids = resolve_problems(&storage, &problems)?;
+            let run = run_eval(&db, &recipe, &ids, baseline.as_deref())?;
+            emit(&run, &format!("Eval: {}", recipe), &output)
+        }
+        Command::Compare {
+            recipe_a,
+            recipe_b,
+            fresh,
+            problems,
+            output,
+        } => {
+            let (run_a, run_b) = if fresh {
+                let ids = resolve_problems(&storage, &problems)?;
+                let a = run_eval(&db, &recipe_a, &ids, None)?;
+                let b = run_eval(&db, &recipe_b, &ids, Some(&recipe_a))?;
+                (a.run_id, b.run_id)
+            } else {
+                (latest_run_id(&storage, &recipe_a)?, latest_run_id(&storage, &recipe_b)?)
+            };
+            let run = compare_runs(&storage, &run_a, &run_b)?;
+            emit(&run, &format!("Compare: {} vs {}", recipe_b, recipe_a), &output)
+        }
+        Command::Report { run_id, recipe, output } => {
+            let run_id = match (run_id, recipe) {
+                (Some(id), _) => id,
+                (None, Some(recipe)) => latest_run_id(&storage, &recipe)?,
+                (None, None) => storage
+                    .list_eval_runs(None)
+                    .map_err(|e| e.to_string())?
+                    .into_iter()
+                    .next()
+                    .map(|r| r.run_id)
+                    .ok_or("No eval runs stored yet")?,
+            };
+            let run = load_run(&storage, &run_id)?;
+            let recipe = run.reports.first().map(|r| r.recipe_id.clone()).unwrap_or_default();
+            emit(&run, &format!("Eval: {}", recipe), &output)
+        }
+        Command::Ab {
+            recipe_a,
+            recipe_b,
+            target_a,
+            target_b,
+            repeats,
+            alpha,
+            bootstrap,
+            seed,
+            fail_if_worse,
+            show,
+            problems,
+            format,
+            output,
+        } => {
+            let report_format =
+                ReportFormat::parse(&format).ok_or_else(|| format!("Unknown report format: {}", format))?;
+            let comparison = match show {
+                Some(id) => load_comparison(&storage, &id)?,
+                None => {
+                    let ids = resolve_problems(&storage, &problems)?;
+                    let options = AbOptions {
+                        repeats,
+                        alpha,
+                        bootstrap_iterations: bootstrap,
+                        seed,
+                    };
+                    run_ab(
+                        &db,
+                        &AbArm::new(&recipe_a, target_a.as_deref()),
+                        &AbArm::new(&recipe_b, target_b.as_deref()),
+                        &ids,
+                        &options,
+                    )?
                 }
+            };
+            let rendered = render_comparison(&comparison, report_format);
+            match &output {
+                Some(path) => std::fs::write(path, rendered).map_err(|e| format!("{}: {}", path.display(), e))?,
+                None => print!("{}", rendered),
             }
-            "--recipe" => {
-                i += 1;
-                if i < args.len() {
-                    recipe_id = args[i].clone();
-                }
+            let worse = comparison
+                .dimensions
+                .iter()
+                .any(|d| d.dimension == "total" && d.verdict == Verdict::ABetter);
+            if fail_if_worse && worse {
+                eprintln!("Regression: {} is significantly worse than {}", comparison.arm_b.label(), comparison.arm_a.label());
+                return Ok(ExitCode::from(2));
             }
-            "--problems" => {
-                i += 1;
-                if i < args.len() {
-                    problem_ids = args[i].split(',').map(String::from).collect();
-                }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ListComparisons => {
+            for c in storage.list_eval_comparisons().map_err(|e| e.to_string())? {
+                println!(
+                    "{}\t{} vs {}\tB beats A: {}\t{}",
+                    c.id,
+                    c.label_b,
+                    c.label_a,
+                    if c.b_beats_a { "yes" } else { "no" },
+                    c.created_at
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Tournament {
+            entrants,
+            judge,
+            k_factor,
+            seed,
+            problems,
+            format,
+            output,
+        } => {
+            let report_format =
+                ReportFormat::parse(&format).ok_or_else(|| format!("Unknown report format: {}", format))?;
+            let ids = resolve_problems(&storage, &problems)?;
+            let arms: Vec<AbArm> = entrants
+                .iter()
+                .map(|e| match e.split_once('@') {
+                    Some((recipe, target)) => AbArm::new(recipe, Some(target)),
+                    None => AbArm::new(e, None),
+                })
+                .collect();
+            let tournament = run_tournament(&db, &arms, &judge, &ids, &TournamentOptions { seed, k_factor })?;
+            for failure in &tournament.failures {
+                eprintln!("{} / {}: {}", failure.problem_id, failure.provider, failure.error);
+            }
+            eprintln!("{} matches judged by {}", tournament.matches.len(), tournament.judge);
+            write_output(&render_leaderboard(&tournament.leaderboard, report_format), output.as_deref())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Leaderboard {
+            recompute,
+            format,
+            output,
+        } => {
+            let report_format =
+                ReportFormat::parse(&format).ok_or_else(|| format!("Unknown report format: {}", format))?;
+            let ratings = match recompute {
+                Some(k) => recompute_ratings(&storage, k)?,
+                None => leaderboard(&storage)?,
+            };
+            write_output(&render_leaderboard(&ratings, report_format), output.as_deref())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ListRuns { recipe } => {
+            for r in storage.list_eval_runs(recipe.as_deref()).map_err(|e| e.to_string())? {
+                println!(
+                    "{}\t{}\t{} results\tmean total {:.2}\t{}",
+                    r.run_id, r.recipe_id, r.result_count, r.mean_total, r.created_at
+                );
             }
-            _ => {}
+            Ok(ExitCode::SUCCESS)
         }
-        i += 1;
     }
+}
 
-    if db_path.is_empty() || recipe_id.is_empty() {
-        eprintln!("Usage: eval --db <path> --recipe <id> --problems <id1,id2,...>");
-        std::process::exit(1);
+fn dataset_command(storage: &Storage, cmd: DatasetCommand) -> Result<(), String> {
+    match cmd {
+        DatasetCommand::Import { name, file, format } => {
+            let n = import_dataset(storage, &name, &file, dataset_format(&file, format.as_deref())?)?;
+            println!("Imported {} problems into {}", n, name);
+        }
+        DatasetCommand::Export { name, file, format } => {
+            let n = export_dataset(storage, &name, &file, dataset_format(&file, format.as_deref())?)?;
+            println!("Exported {} problems from {}", n, name);
+        }
+        DatasetCommand::Harvest { name, feedback } => {
+            let report = harvest_from_chats(storage, &name, &feedback)?;
+            println!("Harvested {} problems into {}", report.harvested, name);
+            if report.existing > 0 {
+                println!("Kept {} already in {}", report.existing, name);
+            }
+            if report.elsewhere > 0 {
+                println!("Skipped {} already in another dataset", report.elsewhere);
+            }
+        }
+        DatasetCommand::List => {
+            for ds in storage.list_datasets().map_err(|e| e.to_string())? {
+                let count = storage.list_dataset_problems(&ds.id).map_err(|e| e.to_string())?.len();
+                println!("{}\t{} problems", ds.name, count);
+            }
+        }
     }
+    Ok(())
+}
 
-    match run_eval(&db_path, &recipe_id, &problem_ids) {
-        Ok(reports) => {
-            println!("Eval complete: {} reports", reports.len());
-            for r in reports {
-                println!("  {} {}: total={}", r.recipe_id, r.bundle_id, r.score_card.total);
-            }
+fn dataset_format(file: &Path, format: Option<&str>) -> Result<DatasetFormat, String> {
+    match format {
+        Some(f) => DatasetFormat::parse(f),
+        None => DatasetFormat::from_path(file),
+    }
+    .ok_or_else(|| "Unknown dataset format - use --format jsonl|csv".to_string())
+}
+
+fn resolve_problems(storage: &Storage, args: &ProblemArgs) -> Result<Vec<String>, String> {
+    match &args.dataset {
+        Some(name) => Ok(load_dataset(storage, name)?.into_iter().map(|p| p.id).collect()),
+        None if !args.problems.is_empty() => Ok(args.problems.clone()),
+        None => Err("Pass --problems or --dataset".to_string()),
+    }
+}
+
+fn write_output(rendered: &str, output: Option<&Path>) -> Result<(), String> {
+    match output {
+        Some(path) => std::fs::write(path, rendered).map_err(|e| format!("{}: {}", path.display(), e)),
+        None => {
+            print!("{}", rendered);
+            Ok(())
         }
-        Err(e) => {
-            eprintln!("Eval error: {}", e);
-            std::process::exit(1);
+    }
+}
+
+/// Print or write the report, then apply the regression gate
+fn emit(run: &EvalRun, title: &str, output: &OutputArgs) -> Result<ExitCode, String> {
+    let format = ReportFormat::parse(&output.format)
+        .ok_or_else(|| format!("Unknown report format: {} (table, md, json, junit, html)", output.format))?;
+    let rendered = render(run, title, format, output.fail_if_regression);
+    match &output.output {
+        Some(path) => std::fs::write(path, rendered).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => print!("{}", rendered),
+    }
+
+    if let Some(limit) = output.fail_if_regression {
+        match total_change_pct(&run.reports) {
+            Some(change) if change < -limit => {
+                eprintln!("Regression: total score changed {:+.1}% (limit -{}%)", change, limit);
+                return Ok(ExitCode::from(2));
+            }
+            Some(_) => {}
+            None => eprintln!("Warning: no baseline results to compare against, regression gate not applied"),
         }
     }
+    let failed_assertions = run
+        .reports
+        .iter()
+        .flat_map(|r| &r.assertions)
+        .filter(|a| !a.passed)
+        .count();
+    if output.fail_on_assertions && failed_assertions > 0 {
+        eprintln!("{} assertion(s) failed", failed_assertions);
+        return Ok(ExitCode::from(2));
+    }
+    Ok(ExitCode::SUCCESS)
 }
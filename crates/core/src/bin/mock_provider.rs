@@ -0,0 +1,509 @@
+//! Mock provider - serves OpenAI, Anthropic and Gemini wire formats on localhost with scripted faults
+//! Usage: cargo run -p idea-engine-core --bin mock-provider -- [--port 8787] [--script steps.json]
+//! Point the adapters at it with OPENAI_BASE_URL / ANTHROPIC_BASE_URL / GEMINI_BASE_URL=http://127.0.0.1:8787
+//! Control endpoints: POST /__mock/script (replace the step queue), GET /__mock/requests, POST /__mock/reset
+
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Parser)]
+#[command(name = "mock-provider", about = "Local OpenAI/Anthropic/Gemini stand-in with fault injection")]
+struct Cli {
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    /// 0 picks a free port
+    #[arg(long, default_value_t = 8787)]
+    port: u16,
+    /// JSON array of steps served in order before falling back to `ok`
+    #[arg(long)]
+    script: Option<PathBuf>,
+}
+
+/// What the next matching request gets
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Scenario {
+    /// A valid response; `bundle` replaces the built-in canned bundle
+    Ok {
+        #[serde(default)]
+        bundle: Option<Value>,
+    },
+    /// Verbatim model output, e.g. a judge verdict or JSON with the wrong shape
+    Text { content: String },
+    /// The bundle wrapped in a ```json fence
+    Fenced,
+    /// Model output that is not JSON at all
+    Malformed,
+    /// Model output cut off halfway, reported as stopped by the token limit
+    TruncatedContent,
+    /// HTTP body shorter than its Content-Length, then the connection closes
+    TruncatedBody,
+    RateLimit {
+        #[serde(default = "default_retry_after")]
+        retry_after: u64,
+    },
+    ServerError {
+        #[serde(default = "default_status")]
+        status: u16,
+        #[serde(default)]
+        message: Option<String>,
+    },
+    /// The provider refuses: Gemini `finishReason` (or `promptFeedback.blockReason` with `prompt`),
+    /// OpenAI `content_filter`, Anthropic `refusal`
+    Blocked {
+        #[serde(default = "default_block_reason")]
+        reason: String,
+        #[serde(default)]
+        prompt: bool,
+    },
+    /// Wait, then serve `then` (default: ok)
+    Slow {
+        delay_ms: u64,
+        #[serde(default)]
+        then: Option<Box<Scenario>>,
+    },
+}
+
+fn default_retry_after() -> u64 {
+    1
+}
+
+fn default_block_reason() -> String {
+    "SAFETY".to_string()
+}
+
+fn default_status() -> u16 {
+    500
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Step {
+    /// Only requests for this provider consume the step
+    #[serde(default)]
+    provider: Option<String>,
+    /// Serve the scenario this many times
+    #[serde(default = "default_times")]
+    times: u32,
+    #[serde(flatten)]
+    scenario: Scenario,
+}
+
+fn default_times() -> u32 {
+    1
+}
+
+#[derive(Default)]
+struct MockState {
+    steps: VecDeque<Step>,
+    requests: Vec<Value>,
+}
+
+impl MockState {
+    fn next_scenario(&mut self, provider: &str) -> Scenario {
+        let Some(pos) = self
+            .steps
+            .iter()
+            .position(|s| s.provider.as_deref().is_none_or(|p| p == provider))
+        else {
+            return Scenario::Ok { bundle: None };
+        };
+        let step = &mut self.steps[pos];
+        let scenario = step.scenario.clone();
+        step.times = step.times.saturating_sub(1);
+        if step.times == 0 {
+            self.steps.remove(pos);
+        }
+        scenario
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// Send only this many body bytes while announcing the full length
+    truncate_to: Option<usize>,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.to_string().into_bytes(),
+            truncate_to: None,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let cli = Cli::parse();
+    let mut state = MockState::default();
+    if let Some(path) = &cli.script {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let steps: Vec<Step> = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        state.steps = steps.into();
+    }
+    let state = Arc::new(Mutex::new(state));
+
+    let listener = TcpListener::bind((cli.host.as_str(), cli.port))
+        .await
+        .map_err(|e| e.to_string())?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    println!("mock-provider listening on http://{}", addr);
+
+    loop {
+        let (stream, _) = listener.accept().await.map_err(|e| e.to_string())?;
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, state).await {
+                eprintln!("mock-provider: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, state: Arc<Mutex<MockState>>) -> Result<(), String> {
+    let request = read_request(&mut stream).await?;
+    let response = route(&request, &state).await;
+    write_response(&mut stream, response).await
+}
+
+async fn route(request: &Request, state: &Arc<Mutex<MockState>>) -> Response {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let body: Value = serde_json::from_slice(&request.body).unwrap_or(Value::Null);
+
+    match (request.method.as_str(), path) {
+        ("POST", "/__mock/script") => match serde_json::from_value::<Vec<Step>>(body) {
+            Ok(steps) => {
+                state.lock().unwrap().steps = steps.into();
+                Response::json(200, json!({ "ok": true }))
+            }
+            Err(e) => Response::json(400, json!({ "error": e.to_string() })),
+        },
+        ("GET", "/__mock/requests") => Response::json(200, Value::Array(state.lock().unwrap().requests.clone())),
+        ("POST", "/__mock/reset") => {
+            *state.lock().unwrap() = MockState::default();
+            Response::json(200, json!({ "ok": true }))
+        }
+        ("GET", "/v1/models") => Response::json(200, listed_models()),
+        ("GET", "/v1beta/models") => Response::json(200, listed_gemini_models()),
+        ("POST", "/v1/chat/completions") => provider_response("openai", path, body, state).await,
+        ("POST", "/v1/messages") => provider_response("anthropic", path, body, state).await,
+        ("POST", p) if p.starts_with("/v1beta/models/") && p.ends_with(":generateContent") => {
+            provider_response("gemini", path, body, state).await
+        }
+        _ => Response::json(404, json!({ "error": format!("No mock route for {} {}", request.method, path) })),
+    }
+}
+
+async fn provider_response(provider: &str, path: &str, body: Value, state: &Arc<Mutex<MockState>>) -> Response {
+    let model = match provider {
+        "gemini" => path
+            .trim_start_matches("/v1beta/models/")
+            .trim_end_matches(":generateContent")
+            .to_string(),
+        _ => body["model"].as_str().unwrap_or_default().to_string(),
+    };
+    // Anthropic forced tool use: answer with a tool_use block instead of text
+    let tool = body["tool_choice"]["name"].as_str().map(String::from);
+    let mut scenario = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(json!({ "provider": provider, "path": path, "body": body }));
+        state.next_scenario(provider)
+    };
+
+    while let Scenario::Slow { delay_ms, then } = scenario {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        scenario = then.map(|s| *s).unwrap_or(Scenario::Ok { bundle: None });
+    }
+
+    let bundle_text = |bundle: Option<Value>| bundle.unwrap_or_else(canned_bundle).to_string();
+    let (content, truncated) = match scenario {
+        Scenario::Ok { bundle } => (bundle_text(bundle), false),
+        Scenario::Text { content } => (content, false),
+        Scenario::Fenced => (format!("```json\n{}\n```", bundle_text(None)), false),
+        Scenario::Malformed => ("Sure! Here are some ideas: {ideas: [oops".to_string(), false),
+        Scenario::TruncatedContent => {
+            let full = bundle_text(None);
+            (full[..full.len() / 2].to_string(), true)
+        }
+        Scenario::TruncatedBody => {
+            let mut response = Response::json(200, success_body(provider, &model, &bundle_text(None), false, tool.as_deref()));
+            response.truncate_to = Some(response.body.len() / 2);
+            return response;
+        }
+        Scenario::RateLimit { retry_after } => {
+            let mut response = Response::json(429, error_body(provider, 429, "Rate limit exceeded"));
+            response.headers.push(("Retry-After".into(), retry_after.to_string()));
+            return response;
+        }
+        Scenario::ServerError { status, message } => {
+            let message = message.unwrap_or_else(|| "Internal server error".to_string());
+            return Response::json(status, error_body(provider, status, &message));
+        }
+        Scenario::Blocked { reason, prompt } => {
+            return Response::json(200, blocked_body(provider, &model, &reason, prompt));
+        }
+        Scenario::Slow { .. } => unreachable!("slow scenarios are unwrapped above"),
+    };
+    Response::json(200, success_body(provider, &model, &content, truncated, tool.as_deref()))
+}
+
+/// A successful completion in the provider's wire format. With `tool`, content that parses as a JSON
+/// object becomes that tool's `tool_use` arguments; anything else stays text, like a model ignoring the tool.
+fn success_body(provider: &str, model: &str, content: &str, truncated: bool, tool: Option<&str>) -> Value {
+    let (input_tokens, output_tokens) = (120, content.len() as u64 / 4);
+    let tool_input = tool.and(serde_json::from_str::<Value>(content).ok()).filter(Value::is_object);
+    match provider {
+        "openai" => json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": if truncated { "length" } else { "stop" }
+            }],
+            "usage": {
+                "prompt_tokens": input_tokens,
+                "completion_tokens": output_tokens,
+                "total_tokens": input_tokens + output_tokens
+            }
+        }),
+        "anthropic" => json!({
+            "id": "msg_mock",
+            "type": "message",
+            "role": "assistant",
+            "model": model,
+            "content": match (&tool_input, tool) {
+                (Some(input), Some(name)) => json!([{ "type": "tool_use", "id": "toolu_mock", "name": name, "input": input }]),
+                _ => json!([{ "type": "text", "text": content }]),
+            },
+            "stop_reason": match (truncated, &tool_input) {
+                (true, _) => "max_tokens",
+                (false, Some(_)) => "tool_use",
+                (false, None) => "end_turn",
+            },
+            "usage": { "input_tokens": input_tokens, "output_tokens": output_tokens }
+        }),
+        _ => json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": content }] },
+                "finishReason": if truncated { "MAX_TOKENS" } else { "STOP" }
+            }],
+            "usageMetadata": {
+                "promptTokenCount": input_tokens,
+                "candidatesTokenCount": output_tokens,
+                "totalTokenCount": input_tokens + output_tokens
+            },
+            "modelVersion": model
+        }),
+    }
+}
+
+/// A refusal in the provider's wire format
+fn blocked_body(provider: &str, model: &str, reason: &str, prompt: bool) -> Value {
+    let ratings = json!([
+        { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true },
+        { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" }
+    ]);
+    match provider {
+        "openai" => json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": null },
+                "finish_reason": "content_filter"
+            }]
+        }),
+        "anthropic" => json!({
+            "id": "msg_mock",
+            "type": "message",
+            "role": "assistant",
+            "model": model,
+            "content": [],
+            "stop_reason": "refusal"
+        }),
+        _ if prompt => json!({
+            "promptFeedback": { "blockReason": reason, "safetyRatings": ratings },
+            "modelVersion": model
+        }),
+        _ => json!({
+            "candidates": [{ "finishReason": reason, "safetyRatings": ratings }],
+            "modelVersion": model
+        }),
+    }
+}
+
+/// An error in the provider's wire format
+fn error_body(provider: &str, status: u16, message: &str) -> Value {
+    match provider {
+        "openai" => json!({
+            "error": {
+                "message": message,
+                "type": if status == 429 { "rate_limit_exceeded" } else { "server_error" }
+            }
+        }),
+        "anthropic" => json!({
+            "type": "error",
+            "error": {
+                "type": if status == 429 { "rate_limit_error" } else { "api_error" },
+                "message": message
+            }
+        }),
+        _ => json!({
+            "error": {
+                "code": status,
+                "message": message,
+                "status": if status == 429 { "RESOURCE_EXHAUSTED" } else { "INTERNAL" }
+            }
+        }),
+    }
+}
+
+/// List-models reply in the OpenAI and Anthropic shape, with one model no catalog knows
+fn listed_models() -> Value {
+    json!({
+        "data": [
+            { "id": "gpt-4o-mini", "type": "model", "display_name": "GPT-4o mini" },
+            { "id": "claude-3-5-haiku-20241022", "type": "model", "display_name": "Claude Haiku 3.5" },
+            { "id": "mock-large", "type": "model", "display_name": "Mock Large" },
+            { "id": "text-embedding-3-small", "type": "model" }
+        ]
+    })
+}
+
+fn listed_gemini_models() -> Value {
+    json!({
+        "models": [
+            {
+                "name": "models/gemini-1.5-flash",
+                "inputTokenLimit": 1048576,
+                "outputTokenLimit": 8192,
+                "supportedGenerationMethods": ["generateContent", "countTokens"]
+            },
+            {
+                "name": "models/mock-large",
+                "inputTokenLimit": 32768,
+                "outputTokenLimit": 2048,
+                "supportedGenerationMethods": ["generateContent"]
+            },
+            {
+                "name": "models/text-embedding-004",
+                "inputTokenLimit": 2048,
+                "outputTokenLimit": 1,
+                "supportedGenerationMethods": ["embedContent"]
+            }
+        ]
+    })
+}
+
+fn canned_bundle() -> Value {
+    json!({
+        "ideas": [
+            {
+                "title": "Weekly digest",
+                "description": "Summarise the week's saved items into one email",
+                "rationale": "Low effort, reuses existing data"
+            },
+            {
+                "title": "Browser extension",
+                "description": "Save items from any page with one click"
+            }
+        ],
+        "step_plan": [
+            { "order": 1, "action": "Define the digest format", "details": "Pick sections and ordering" },
+            { "order": 2, "action": "Build the summariser" },
+            { "order": 3, "action": "Schedule the weekly send" }
+        ],
+        "risks": [
+            { "description": "Emails land in spam", "severity": "medium", "mitigation": "Use a verified sending domain" }
+        ],
+        "dependencies": ["email provider", "cron"],
+        "effort": { "time": "2 weeks", "cost": "low", "complexity": "medium" },
+        "next_actions": [
+            { "action": "Draft a sample digest", "priority": "immediate" }
+        ]
+    })
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("connection closed before headers".to_string());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let content_length = lines
+        .filter_map(|l| l.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    Ok(Request { method, path, body })
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> Result<(), String> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason(response.status));
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+    stream.write_all(head.as_bytes()).await.map_err(|e| e.to_string())?;
+    let sent = response.truncate_to.unwrap_or(response.body.len());
+    stream
+        .write_all(&response.body[..sent])
+        .await
+        .map_err(|e| e.to_string())?;
+    stream.shutdown().await.map_err(|e| e.to_string())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Status",
+    }
+}
@@ -0,0 +1,306 @@
+//! Model catalog - context window, output limit, capabilities, prices and deprecation per provider and model
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+const BUNDLED: &str = include_str!("../models.json");
+/// Path of a catalog file merged over the bundled one and the desktop override file
+pub const CATALOG_ENV: &str = "IDEA_ENGINE_MODELS";
+/// Name of the override file the desktop app keeps in its data dir
+pub const OVERRIDE_FILE: &str = "models.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelStatus {
+    #[default]
+    Active,
+    /// Still served, scheduled for removal
+    Deprecated,
+    /// No longer served; requests are rejected before they are sent
+    Retired,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub provider: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    /// Native schema-constrained output (json_schema, forced tool use, responseSchema); unknown when None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_output: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streaming: Option<bool>,
+    /// USD per million input tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_usd_per_mtok: Option<f64>,
+    /// USD per million output tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_usd_per_mtok: Option<f64>,
+    #[serde(default)]
+    pub status: ModelStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<String>,
+}
+
+impl ModelInfo {
+    /// Price of a call in USD; None while either price is unknown
+    pub fn cost_usd(&self, input_tokens: u64, output_tokens: u64) -> Option<f64> {
+        let input = self.input_usd_per_mtok? * input_tokens as f64;
+        let output = self.output_usd_per_mtok? * output_tokens as f64;
+        Some((input + output) / 1_000_000.0)
+    }
+
+    /// "provider:model is deprecated, use ..." for a model that is not active
+    pub fn deprecation_warning(&self) -> Option<String> {
+        let state = match self.status {
+            ModelStatus::Active => return None,
+            ModelStatus::Deprecated => "deprecated",
+            ModelStatus::Retired => "retired",
+        };
+        let mut warning = format!("{}:{} is {}", self.provider, self.model, state);
+        if let Some(replacement) = &self.replaced_by {
+            warning = format!("{}, use {}", warning, replacement);
+        }
+        Some(warning)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModelCatalog {
+    models: Vec<ModelInfo>,
+}
+
+impl ModelCatalog {
+    /// The catalog shipped with the crate
+    pub fn bundled() -> Self {
+        Self {
+            models: serde_json::from_str(BUNDLED).expect("bundled models.json is valid"),
+        }
+    }
+
+    /// The bundled catalog with the file at `override_path` merged over it, then the `IDEA_ENGINE_MODELS`
+    /// file, so an explicitly supplied catalog wins. Files that do not exist are skipped.
+    pub fn load(override_path: Option<&Path>) -> Result<Self, String> {
+        let env_path = std::env::var(CATALOG_ENV).ok().filter(|p| !p.trim().is_empty());
+        let mut catalog = Self::bundled();
+        for path in [override_path, env_path.as_deref().map(Path::new)].into_iter().flatten() {
+            if path.exists() {
+                catalog = catalog.with_overrides(&read_entries(path)?)?;
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Merge partial entries over the catalog: fields an entry sets replace the matching model's,
+    /// entries for unknown models are added
+    pub fn with_overrides(mut self, overrides: &[Value]) -> Result<Self, String> {
+        for entry in overrides {
+            let (provider, model) = entry_key(entry).ok_or("Catalog entry needs provider and model")?;
+            let index = self.models.iter().position(|m| m.provider == provider && m.model == model);
+            let mut merged = match index {
+                Some(i) => serde_json::to_value(&self.models[i]).map_err(|e| e.to_string())?,
+                None => Value::Object(Default::default()),
+            };
+            merge_fields(&mut merged, entry);
+            let info: ModelInfo = serde_json::from_value(merged).map_err(|e| format!("{}:{}: {}", provider, model, e))?;
+            match index {
+                Some(i) => self.models[i] = info,
+                None => self.models.push(info),
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn get(&self, provider: &str, model: &str) -> Option<&ModelInfo> {
+        self.models.iter().find(|m| m.provider == provider && m.model == model)
+    }
+
+    pub fn models(&self) -> &[ModelInfo] {
+        &self.models
+    }
+}
+
+fn cell() -> &'static RwLock<Arc<ModelCatalog>> {
+    static CATALOG: OnceLock<RwLock<Arc<ModelCatalog>>> = OnceLock::new();
+    CATALOG.get_or_init(|| {
+        // A broken override file must not stop generation; the bundled catalog still applies
+        let catalog = ModelCatalog::load(None).unwrap_or_else(|_| ModelCatalog::bundled());
+        RwLock::new(Arc::new(catalog))
+    })
+}
+
+/// The catalog adapters and cost estimates read: bundled, plus the `IDEA_ENGINE_MODELS` file until
+/// `install` replaces it with one from `ModelCatalog::load`, which keeps that file merged in
+pub fn catalog() -> Arc<ModelCatalog> {
+    Arc::clone(&cell().read().unwrap())
+}
+
+/// Make `catalog` the one every later lookup sees
+pub fn install(catalog: ModelCatalog) {
+    *cell().write().unwrap() = Arc::new(catalog);
+}
+
+/// Catalog entry for a provider and model
+pub fn lookup(provider: &str, model: &str) -> Option<ModelInfo> {
+    catalog().get(provider, model).cloned()
+}
+
+/// A model as a provider's list-models endpoint reports it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListedModel {
+    pub provider: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+}
+
+/// What a refresh changed for one provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshReport {
+    pub provider: String,
+    /// Listed models the catalog did not know
+    pub added: Vec<String>,
+    /// Catalog models the provider no longer lists; left as they are, listings can be per account
+    pub missing: Vec<String>,
+}
+
+/// Models a provider serves, from its list-models endpoint. OpenAI's list is cut to chat models;
+/// only Gemini reports limits.
+pub async fn list_models(provider: &str, api_key: &str, base_url: Option<&str>) -> Result<Vec<ListedModel>, String> {
+    let client = reqwest::Client::new();
+    let request = match provider {
+        "openai" => client
+            .get(format!("{}/v1/models", base_url.unwrap_or("https://api.openai.com")))
+            .header("Authorization", format!("Bearer {}", api_key)),
+        "anthropic" => client
+            .get(format!("{}/v1/models?limit=1000", base_url.unwrap_or("https://api.anthropic.com")))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01"),
+        "gemini" => client
+            .get(format!(
+                "{}/v1beta/models?pageSize=1000",
+                base_url.unwrap_or("https://generativelanguage.googleapis.com")
+            ))
+            .header("x-goog-api-key", api_key),
+        _ => return Err(format!("Unknown provider: {}", provider)),
+    };
+    let res = request.send().await.map_err(|e| e.to_string())?;
+    let status = res.status();
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("{} list models: {} {}", provider, status, text));
+    }
+    let body: Value = serde_json::from_str(&text).map_err(|e| format!("{} list models: {}", provider, e))?;
+
+    let listed = |model: &str, context_window: Option<u32>, max_output_tokens: Option<u32>| ListedModel {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        context_window,
+        max_output_tokens,
+    };
+    let items = |key: &str| body[key].as_array().cloned().unwrap_or_default();
+    let models = match provider {
+        "openai" => items("data")
+            .iter()
+            .filter_map(|m| m["id"].as_str())
+            .filter(|id| is_openai_chat_model(id))
+            .map(|id| listed(id, None, None))
+            .collect(),
+        "anthropic" => items("data")
+            .iter()
+            .filter_map(|m| m["id"].as_str())
+            .map(|id| listed(id, None, None))
+            .collect(),
+        _ => items("models")
+            .iter()
+            .filter(|m| {
+                m["supportedGenerationMethods"]
+                    .as_array()
+                    .is_some_and(|methods| methods.iter().any(|x| x == "generateContent"))
+            })
+            .filter_map(|m| {
+                let id = m["name"].as_str()?.trim_start_matches("models/");
+                let limit = |key: &str| m[key].as_u64().map(|n| n as u32);
+                Some(listed(id, limit("inputTokenLimit"), limit("outputTokenLimit")))
+            })
+            .collect(),
+    };
+    Ok(models)
+}
+
+/// Fetch a provider's models, write them into the override file at `path` and install the result
+pub async fn refresh(provider: &str, api_key: &str, base_url: Option<&str>, path: &Path) -> Result<RefreshReport, String> {
+    let listed = list_models(provider, api_key, base_url).await?;
+    let current = catalog();
+    let added = listed
+        .iter()
+        .filter(|l| current.get(provider, &l.model).is_none())
+        .map(|l| l.model.clone())
+        .collect();
+    let missing = current
+        .models()
+        .iter()
+        .filter(|m| m.provider == provider && m.status != ModelStatus::Retired)
+        .filter(|m| !listed.iter().any(|l| l.model == m.model))
+        .map(|m| m.model.clone())
+        .collect();
+
+    let entries: Vec<Value> = listed
+        .iter()
+        .map(|l| serde_json::to_value(l).map_err(|e| e.to_string()))
+        .collect::<Result<_, _>>()?;
+    save_overrides(path, &entries)?;
+    install(ModelCatalog::load(Some(path))?);
+    Ok(RefreshReport {
+        provider: provider.to_string(),
+        added,
+        missing,
+    })
+}
+
+/// Merge entries into the override file at `path`, keeping fields the user set by hand
+pub fn save_overrides(path: &Path, entries: &[Value]) -> Result<(), String> {
+    let mut existing = if path.exists() { read_entries(path)? } else { Vec::new() };
+    for entry in entries {
+        let key = entry_key(entry);
+        match existing.iter_mut().find(|e| entry_key(e) == key) {
+            Some(current) => merge_fields(current, entry),
+            None => existing.push(entry.clone()),
+        }
+    }
+    let json = serde_json::to_string_pretty(&existing).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())
+}
+
+fn read_entries(path: &Path) -> Result<Vec<Value>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn entry_key(entry: &Value) -> Option<(String, String)> {
+    Some((entry["provider"].as_str()?.to_string(), entry["model"].as_str()?.to_string()))
+}
+
+/// Copy the fields of `from` that are not null into `into`; listings never clear a known value
+fn merge_fields(into: &mut Value, from: &Value) {
+    if let (Some(into), Some(from)) = (into.as_object_mut(), from.as_object()) {
+        for (key, value) in from.iter().filter(|(_, v)| !v.is_null()) {
+            into.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// OpenAI lists embedding, audio, image and moderation models alongside chat ones
+fn is_openai_chat_model(id: &str) -> bool {
+    let reasoning = id.strip_prefix('o').is_some_and(|rest| rest.starts_with(char::is_numeric));
+    let chat = reasoning || id.starts_with("gpt-") || id.starts_with("chatgpt-");
+    let other = ["audio", "realtime", "tts", "transcribe", "image", "search", "instruct"];
+    chat && !other.iter().any(|o| id.contains(o))
+}
@@ -0,0 +1,171 @@
+//! Clarifying questions - find what an underspecified problem leaves out before fanning out
+
+use crate::adapters::ProviderAdapter;
+use crate::json_repair::parse_lenient;
+use crate::orchestrator::DEFAULT_TIMEOUT_SECS;
+use crate::request::{CompletionRequest, GenerationParams};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// At most this many questions are asked, so the stage stays quick to answer
+pub const MAX_QUESTIONS: usize = 5;
+
+const CLARIFY_SYSTEM_PROMPT: &str = "You review problem statements before ideas are generated for them. \
+Find what is missing or ambiguous among: goal, budget, timeline, audience, constraints. \
+Ask only about what is actually missing, at most 5 short questions; ask none if the problem is clear. \
+Offer up to 4 likely answers per question where that helps. \
+Respond with JSON only: {\"questions\": [{\"aspect\": \"goal\" | \"budget\" | \"timeline\" | \"audience\" | \"constraints\" | \"other\", \"question\": \"...\", \"options\": [\"...\"]}]}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aspect {
+    Goal,
+    Budget,
+    Timeline,
+    Audience,
+    Constraints,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClarifyingQuestion {
+    pub aspect: Aspect,
+    pub question: String,
+    /// Suggested answers the user can pick from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClarifyingAnswer {
+    pub question: String,
+    pub answer: String,
+}
+
+/// Where a set of questions came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestionSource {
+    Model,
+    Heuristic,
+}
+
+struct Check {
+    aspect: Aspect,
+    pattern: &'static str,
+    question: &'static str,
+    options: &'static [&'static str],
+}
+
+const CHECKS: [Check; 5] = [
+    Check {
+        aspect: Aspect::Goal,
+        pattern: r"\b(goal|aim|so that|in order to|objective|target|increase|reduce|improve|grow|double|launch|achieve|reach|success)\w*|\d+\s?%",
+        question: "What outcome would make this a success?",
+        options: &[],
+    },
+    Check {
+        aspect: Aspect::Budget,
+        pattern: r"[$€£]|\b(budget|price|spend|afford|cheap|for free|free of charge|low[- ]cost|costs? (under|less than|at most|up to)|funding|money|\d+\s?k\b)",
+        question: "What budget is available?",
+        options: &["None, time only", "Under $1k", "$1k-$10k", "More than $10k"],
+    },
+    Check {
+        aspect: Aspect::Timeline,
+        pattern: r"\b(day|week|month|quarter|year|deadline|timeline|asap|sprint|q[1-4]|by (jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)|tomorrow|weekend)\w*",
+        question: "By when does this need to be done?",
+        options: &["This week", "This month", "This quarter", "No deadline"],
+    },
+    Check {
+        aspect: Aspect::Audience,
+        pattern: r"\b(user|customer|client|audience|team|student|people|developer|employee|reader|buyer|b2b|b2c|market|for (me|my|our|us))\w*",
+        question: "Who is this for?",
+        options: &[],
+    },
+    Check {
+        aspect: Aspect::Constraints,
+        pattern: r"\b(must|cannot|can't|without|only (use|with|on)|constraint|limit|require|no more than|at most|stack|existing|legal|compliance)\w*",
+        question: "Are there constraints to respect, such as tools, skills, regulations or things to avoid?",
+        options: &[],
+    },
+];
+
+/// Problems shorter than this many words get every question; they rarely say enough
+const SHORT_PROBLEM_WORDS: usize = 6;
+
+/// Local, free check: one question per aspect the problem never mentions
+pub fn lint_problem(problem: &str) -> Vec<ClarifyingQuestion> {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        CHECKS
+            .iter()
+            .map(|c| Regex::new(&format!("(?i){}", c.pattern)).expect("valid clarify pattern"))
+            .collect()
+    });
+    let short = problem.split_whitespace().count() < SHORT_PROBLEM_WORDS;
+    CHECKS
+        .iter()
+        .zip(patterns)
+        .filter(|(_, pattern)| short || !pattern.is_match(problem))
+        .map(|(check, _)| ClarifyingQuestion {
+            aspect: check.aspect,
+            question: check.question.to_string(),
+            options: check.options.iter().map(ToString::to_string).collect(),
+        })
+        .collect()
+}
+
+/// Ask `adapter` - ideally a cheap, fast model - which details the problem is missing. A call that
+/// takes longer than the orchestrator's timeout is abandoned.
+pub async fn ask_model(adapter: &dyn ProviderAdapter, problem: &str) -> Result<Vec<ClarifyingQuestion>, String> {
+    let request = CompletionRequest::new(CLARIFY_SYSTEM_PROMPT, &format!("Problem:\n{}", problem)).with_params(
+        GenerationParams {
+            temperature: Some(0.2),
+            ..Default::default()
+        },
+    );
+    let text = timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS), adapter.complete_text(&request))
+        .await
+        .map_err(|_| format!("{}: timed out after {}s", adapter.provider_name(), DEFAULT_TIMEOUT_SECS))?
+        .map_err(|e| format!("{}: {}", adapter.provider_name(), e))?;
+    let (value, _) = parse_lenient::<Value>(&text).map_err(|e| format!("Clarifying questions: {}", e))?;
+    let items = if value.is_array() { value } else { value["questions"].clone() };
+    let mut questions: Vec<ClarifyingQuestion> = serde_json::from_value(items).map_err(|e| format!("Clarifying questions: {}", e))?;
+    questions.retain(|q| !q.question.trim().is_empty());
+    questions.truncate(MAX_QUESTIONS);
+    Ok(questions)
+}
+
+/// Questions from `adapter` when given and it answers, otherwise from the local linter. The error
+/// of a failed model call is returned alongside the heuristic questions.
+pub async fn clarifying_questions(
+    adapter: Option<&dyn ProviderAdapter>,
+    problem: &str,
+) -> (Vec<ClarifyingQuestion>, QuestionSource, Option<String>) {
+    let error = match adapter {
+        Some(adapter) => match ask_model(adapter, problem).await {
+            Ok(questions) => return (questions, QuestionSource::Model, None),
+            Err(e) => Some(e),
+        },
+        None => None,
+    };
+    (lint_problem(problem), QuestionSource::Heuristic, error)
+}
+
+/// The problem with the user's answers appended; unanswered questions are left out
+pub fn fold_answers(problem: &str, answers: &[ClarifyingAnswer]) -> String {
+    let answered: Vec<String> = answers
+        .iter()
+        .filter(|a| !a.answer.trim().is_empty())
+        .map(|a| format!("- {} {}", a.question.trim(), a.answer.trim()))
+        .collect();
+    if answered.is_empty() {
+        return problem.to_string();
+    }
+    format!("{}\n\nClarifications:\n{}", problem, answered.join("\n"))
+}
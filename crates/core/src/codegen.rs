@@ -0,0 +1,42 @@
+//! Code generation - TypeScript mirrors of core types and the response JSON Schema for @idea-engine/shared
+
+use crate::response_schema::{response_schema, RESPONSE_SCHEMA_VERSION};
+use crate::schema::{Complexity, Priority, Severity};
+
+const HEADER: &str = "// Generated by `cargo run -p idea-engine-core --bin codegen` from crates/core/src/schema.rs. Do not edit.\n";
+
+/// Generated files as (file name, contents)
+pub fn typescript_files() -> Vec<(String, String)> {
+    let schema = serde_json::to_string_pretty(&response_schema()).unwrap_or_default();
+    vec![
+        ("enums.ts".to_string(), typescript_enums()),
+        (format!("ai-response.v{}.schema.json", RESPONSE_SCHEMA_VERSION), format!("{}\n", schema)),
+        ("responseSchema.ts".to_string(), typescript_response_schema(&schema)),
+    ]
+}
+
+/// The response schema as a typed constant, versioned like the JSON artifact
+pub fn typescript_response_schema(schema: &str) -> String {
+    format!(
+        "{HEADER}\nexport const AI_RESPONSE_SCHEMA_VERSION = {RESPONSE_SCHEMA_VERSION};\n\nexport const AI_RESPONSE_JSON_SCHEMA = {schema} as const;\n"
+    )
+}
+
+/// Const value lists, union types, type guards and zod schemas for the lenient enums. Like `Unknown`
+/// on the Rust side, the unions and schemas accept any other string a provider sent.
+pub fn typescript_enums() -> String {
+    let mut out = String::from(HEADER);
+    out.push_str("\nimport { z } from 'zod';\n");
+    for (name, plural, values) in [
+        ("Severity", "SEVERITIES", Severity::VALUES),
+        ("Priority", "PRIORITIES", Priority::VALUES),
+        ("Complexity", "COMPLEXITIES", Complexity::VALUES),
+    ] {
+        let quoted: Vec<String> = values.iter().map(|v| format!("'{}'", v)).collect();
+        out.push_str(&format!(
+            "\nexport const {plural} = [{}] as const;\nexport type Known{name} = (typeof {plural})[number];\n/** A known value, or the unmapped value a provider sent (`Unknown` in Rust) */\nexport type {name} = Known{name} | (string & {{}});\nexport function is{name}(value: string): value is Known{name} {{\n  return ({plural} as readonly string[]).includes(value);\n}}\nexport const {name}Schema = z.union([z.enum({plural}), z.string()]);\n",
+            quoted.join(", ")
+        ));
+    }
+    out
+}
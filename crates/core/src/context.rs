@@ -0,0 +1,229 @@
+//! Chat context - rebuild earlier turns of a chat as request history, within a token budget
+
+use crate::request::{Message, Role};
+use crate::storage::{MessageRow, Storage};
+use serde_json::Value;
+
+/// History budget when the caller does not set one
+pub const DEFAULT_CONTEXT_TOKENS: usize = 6000;
+/// Share of the budget kept for the one-line-per-turn digest of turns that no longer fit
+const DIGEST_SHARE: usize = 4;
+const DIGEST_LINE_CHARS: usize = 120;
+const DESCRIPTION_CHARS: usize = 160;
+
+/// Rough token count: about four characters per token for English prose
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// The chat's stored messages as history for its next request
+pub fn chat_context(storage: &Storage, chat_id: &str, budget_tokens: usize) -> Result<Vec<Message>, String> {
+    let rows = storage.get_chat_messages(chat_id).map_err(|e| e.to_string())?;
+    Ok(chat_history(&rows, budget_tokens))
+}
+
+/// Turn stored messages into alternating user/assistant turns, oldest first. Assistant turns carry a
+/// compact summary of their bundles rather than the raw JSON. The newest turns are kept whole while
+/// they fit `budget_tokens`; older ones collapse into a short digest at the start. The history
+/// always ends with an assistant turn, since the new prompt follows it.
+pub fn chat_history(rows: &[MessageRow], budget_tokens: usize) -> Vec<Message> {
+    let mut turns: Vec<Message> = rows.iter().filter_map(to_message).collect();
+    // A trailing user turn never got a reply (its run failed); the new prompt takes its place
+    while turns.last().is_some_and(|t| t.role == Role::User) {
+        turns.pop();
+    }
+    let total: usize = turns.iter().map(|t| estimate_tokens(&t.content)).sum();
+    if total <= budget_tokens {
+        return alternate(turns);
+    }
+
+    // Not everything fits: keep the newest turns whole and set a share aside for the digest
+    let digest_budget = budget_tokens / DIGEST_SHARE;
+    let whole_budget = budget_tokens - digest_budget;
+    let mut used = 0;
+    let mut keep_from = turns.len();
+    for (i, turn) in turns.iter().enumerate().rev() {
+        let tokens = estimate_tokens(&turn.content);
+        if used + tokens > whole_budget {
+            break;
+        }
+        used += tokens;
+        keep_from = i;
+    }
+
+    let mut kept = turns[keep_from..].to_vec();
+    // The newest reply alone is over budget: keep its start rather than end on the digest
+    if kept.is_empty() {
+        keep_from = turns.len() - 1;
+        let last = &turns[keep_from];
+        let content: String = last.content.chars().take(whole_budget * 4).collect();
+        kept.push(Message {
+            role: last.role,
+            content,
+        });
+    }
+
+    let mut history = Vec::new();
+    if let Some(digest) = digest(&turns[..keep_from], digest_budget) {
+        history.push(Message::user(digest));
+    }
+    history.extend(kept);
+    alternate(history)
+}
+
+/// Compact, numbered summary of a message's `idea_bundles_json`, so follow-ups can refer to
+/// "bundle 2" or "idea 3". None if the JSON holds no bundles.
+pub fn summarize_bundles(json: &str) -> Option<String> {
+    let bundles: Vec<Value> = serde_json::from_str(json).ok()?;
+    if bundles.is_empty() {
+        return None;
+    }
+    let sections: Vec<String> = bundles
+        .iter()
+        .enumerate()
+        .map(|(i, b)| summarize_bundle(i + 1, b))
+        .collect();
+    Some(sections.join("\n\n"))
+}
+
+pub(crate) fn summarize_bundle(number: usize, bundle: &Value) -> String {
+    let mut lines = vec![format!(
+        "Bundle {} ({}:{})",
+        number,
+        bundle["provider"].as_str().unwrap_or("unknown"),
+        bundle["model"].as_str().unwrap_or("unknown")
+    )];
+    lines.push("Ideas:".to_string());
+    for (i, idea) in array(bundle, &["ideas"]).iter().enumerate() {
+        lines.push(format!(
+            "{}. {} - {}",
+            i + 1,
+            idea["title"].as_str().unwrap_or_default(),
+            clip(idea["description"].as_str().unwrap_or_default(), DESCRIPTION_CHARS)
+        ));
+    }
+    let steps: Vec<&str> = array(bundle, &["stepPlan", "step_plan"])
+        .iter()
+        .filter_map(|s| s["action"].as_str())
+        .collect();
+    if !steps.is_empty() {
+        lines.push(format!("Steps: {}", steps.join(" -> ")));
+    }
+    let risks: Vec<String> = array(bundle, &["risks"])
+        .iter()
+        .map(|r| {
+            format!(
+                "{} ({})",
+                r["description"].as_str().unwrap_or_default(),
+                r["severity"].as_str().unwrap_or("unknown")
+            )
+        })
+        .collect();
+    if !risks.is_empty() {
+        lines.push(format!("Risks: {}", risks.join("; ")));
+    }
+    let deps = array(bundle, &["dependencies"]);
+    if !deps.is_empty() {
+        let deps: Vec<&str> = deps.iter().filter_map(Value::as_str).collect();
+        lines.push(format!("Dependencies: {}", deps.join(", ")));
+    }
+    let effort = &bundle["effort"];
+    if effort.is_object() {
+        lines.push(format!(
+            "Effort: {}, {} complexity",
+            effort["time"].as_str().unwrap_or("unknown"),
+            effort["complexity"].as_str().unwrap_or("unknown")
+        ));
+    }
+    lines.join("\n")
+}
+
+fn to_message(row: &MessageRow) -> Option<Message> {
+    match row.role.as_str() {
+        "user" => Some(Message::user(row.content.clone())),
+        "assistant" => {
+            let content = row
+                .idea_bundles_json
+                .as_deref()
+                .and_then(summarize_bundles)
+                .unwrap_or_else(|| row.content.clone());
+            Some(Message::assistant(content))
+        }
+        _ => None,
+    }
+}
+
+/// One line per dropped turn, newest kept first when the budget runs out
+fn digest(turns: &[Message], budget_tokens: usize) -> Option<String> {
+    let header = "Summary of earlier turns in this chat:";
+    let mut used = estimate_tokens(header);
+    let mut lines = Vec::new();
+    for turn in turns.iter().rev() {
+        let line = match turn.role {
+            Role::User => format!("- User: {}", clip(&turn.content, DIGEST_LINE_CHARS)),
+            Role::Assistant => format!("- Assistant: {}", clip(&idea_titles(&turn.content), DIGEST_LINE_CHARS)),
+        };
+        let tokens = estimate_tokens(&line);
+        if used + tokens > budget_tokens {
+            break;
+        }
+        used += tokens;
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    let omitted = turns.len() - lines.len();
+    lines.reverse();
+    let mut digest = format!("{}\n{}", header, lines.join("\n"));
+    if omitted > 0 {
+        digest = format!("{}\n({} older turns omitted)", digest, omitted);
+    }
+    Some(digest)
+}
+
+/// "ideas: A; B; C" from a bundle summary, or the text itself for a plain reply
+fn idea_titles(summary: &str) -> String {
+    let titles: Vec<&str> = summary
+        .lines()
+        .filter_map(|l| l.split_once(". ").filter(|(n, _)| n.parse::<usize>().is_ok()))
+        .map(|(_, rest)| rest.split(" - ").next().unwrap_or(rest))
+        .collect();
+    if titles.is_empty() {
+        summary.to_string()
+    } else {
+        format!("ideas: {}", titles.join("; "))
+    }
+}
+
+/// Providers want the conversation to open with a user turn and alternate roles: drop leading
+/// assistant turns and merge consecutive turns with the same role
+fn alternate(turns: Vec<Message>) -> Vec<Message> {
+    let mut out: Vec<Message> = Vec::new();
+    for turn in turns.into_iter().skip_while(|t| t.role == Role::Assistant) {
+        match out.last_mut() {
+            Some(last) if last.role == turn.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&turn.content);
+            }
+            _ => out.push(turn),
+        }
+    }
+    out
+}
+
+fn array<'a>(value: &'a Value, keys: &[&str]) -> &'a [Value] {
+    keys.iter()
+        .find_map(|k| value[*k].as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn clip(text: &str, max_chars: usize) -> String {
+    let text = text.trim().replace('\n', " ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let clipped: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+    format!("{}...", clipped.trim_end())
+}
@@ -0,0 +1,226 @@
+//! Decomposition - split a large problem into dependent sub-problems, solve each, reassemble one plan
+
+use crate::adapters::ProviderAdapter;
+use crate::context::summarize_bundle;
+use crate::json_repair::{parse_lenient, Repair};
+use crate::request::CompletionRequest;
+use crate::schema::IdeaBundle;
+use crate::storage::message_bundles;
+use crate::usage::{Attempt, Usage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// More sub-problems than this are cut, so one plan cannot turn into dozens of fan-outs
+pub const MAX_SUB_PROBLEMS: usize = 8;
+
+const PLANNER_SYSTEM_PROMPT: &str = "You split large problems into smaller sub-problems that can be planned separately. \
+Return 2 to 8 sub-problems that together cover the whole problem, each narrow enough for a focused plan. \
+List in depends_on the ids of sub-problems whose results must exist before this one can start. \
+Respond with JSON only: {\"sub_problems\": [{\"id\": \"s1\", \"title\": \"...\", \"description\": \"...\", \"depends_on\": []}]}";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubProblem {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Ids of sub-problems that must be solved first
+    #[serde(default, alias = "dependsOn", alias = "dependencies")]
+    pub depends_on: Vec<String>,
+}
+
+/// One sub-problem with the top-ranked bundle the fan-out produced for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanSection {
+    pub sub_problem: SubProblem,
+    pub bundle: Option<IdeaBundle>,
+    /// (target "provider:model", error) for targets that failed on this sub-problem
+    pub errors: Vec<(String, String)>,
+}
+
+/// A step of the assembled plan, numbered across all sections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub order: i32,
+    pub sub_problem_id: String,
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HierarchicalPlan {
+    /// Sections in dependency order: every section comes after the ones it depends on
+    pub sections: Vec<PlanSection>,
+    pub steps: Vec<PlanStep>,
+    /// Planner output that was corrected, or why the problem was solved whole
+    pub warnings: Vec<String>,
+    /// Every provider call behind the plan, the planner's included
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
+}
+
+impl HierarchicalPlan {
+    /// The top bundle of each section, in the shape stored with chat messages
+    pub fn bundles_json(&self) -> Value {
+        let bundles: Vec<IdeaBundle> = self.sections.iter().filter_map(|s| s.bundle.clone()).collect();
+        message_bundles(&bundles)
+    }
+
+    /// Totals for the whole plan
+    pub fn usage(&self) -> Usage {
+        Usage::from_attempts(&self.attempts)
+    }
+
+    /// Assistant message text stored next to the bundles: the sub-problems in order and any failures
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = self
+            .sections
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let status = if s.bundle.is_some() { "" } else { " (no plan)" };
+                format!("{}. {}{}", i + 1, s.sub_problem.title, status)
+            })
+            .collect();
+        let errors: Vec<String> = self
+            .sections
+            .iter()
+            .flat_map(|s| s.errors.iter().map(move |(p, e)| format!("{} / {}: {}", s.sub_problem.id, p, e)))
+            .collect();
+        let mut summary = format!("Planned {} part(s), {} steps: {}", self.sections.len(), self.steps.len(), parts.join("; "));
+        if !errors.is_empty() {
+            summary = format!("{}. Errors: {}", summary, errors.join("; "));
+        }
+        if !self.warnings.is_empty() {
+            summary = format!("{}. Warnings: {}", summary, self.warnings.join("; "));
+        }
+        summary
+    }
+}
+
+/// Ask the planner for sub-problems and put them in dependency order
+pub async fn plan(planner: &dyn ProviderAdapter, request: &CompletionRequest) -> Result<(Vec<SubProblem>, Vec<String>), String> {
+    let planner_request = CompletionRequest::new(PLANNER_SYSTEM_PROMPT, &format!("Problem:\n{}", request.user_prompt()))
+        .with_history(request.history().to_vec());
+    let text = planner
+        .complete_text(&planner_request)
+        .await
+        .map_err(|e| format!("Planner {}: {}", planner.provider_name(), e))?;
+    let (value, repairs) = parse_lenient::<Value>(&text).map_err(|e| format!("Planner output: {}", e))?;
+    let items = match &value {
+        Value::Array(_) => value.clone(),
+        _ => ["sub_problems", "subProblems", "subproblems"]
+            .iter()
+            .map(|k| value[*k].clone())
+            .find(Value::is_array)
+            .ok_or("Planner output has no sub_problems array")?,
+    };
+    let subs: Vec<SubProblem> = serde_json::from_value(items).map_err(|e| format!("Planner output: {}", e))?;
+    let (subs, mut warnings) = order_sub_problems(subs)?;
+    warnings.extend(
+        repairs
+            .iter()
+            .filter(|r| matches!(r, Repair::ClosedTruncated { .. }))
+            .map(|r| format!("planner {}", r)),
+    );
+    Ok((subs, warnings))
+}
+
+/// Drop duplicate ids and unknown or self dependencies, cap the count, then sort so every
+/// sub-problem follows its dependencies (planner order breaks ties). Fails on a dependency cycle.
+pub fn order_sub_problems(subs: Vec<SubProblem>) -> Result<(Vec<SubProblem>, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let mut seen = HashSet::new();
+    let mut subs: Vec<SubProblem> = subs
+        .into_iter()
+        .filter(|s| {
+            let fresh = seen.insert(s.id.clone());
+            if !fresh {
+                warnings.push(format!("duplicate sub-problem id {} dropped", s.id));
+            }
+            fresh
+        })
+        .collect();
+    if subs.len() > MAX_SUB_PROBLEMS {
+        warnings.push(format!("{} sub-problems cut to {}", subs.len(), MAX_SUB_PROBLEMS));
+        subs.truncate(MAX_SUB_PROBLEMS);
+    }
+    let ids: HashSet<String> = subs.iter().map(|s| s.id.clone()).collect();
+    for sub in &mut subs {
+        let id = sub.id.clone();
+        sub.depends_on.retain(|dep| {
+            let known = *dep != id && ids.contains(dep);
+            if !known {
+                warnings.push(format!("{}: ignored dependency on {}", id, dep));
+            }
+            known
+        });
+    }
+
+    let mut ordered: Vec<SubProblem> = Vec::with_capacity(subs.len());
+    let mut done: HashSet<String> = HashSet::new();
+    while ordered.len() < subs.len() {
+        let next = subs
+            .iter()
+            .find(|s| !done.contains(&s.id) && s.depends_on.iter().all(|d| done.contains(d)))
+            .ok_or_else(|| {
+                let stuck: Vec<&str> = subs.iter().filter(|s| !done.contains(&s.id)).map(|s| s.id.as_str()).collect();
+                format!("Sub-problem dependencies form a cycle among: {}", stuck.join(", "))
+            })?;
+        done.insert(next.id.clone());
+        ordered.push(next.clone());
+    }
+    Ok((ordered, warnings))
+}
+
+/// Prompt for one sub-problem: the whole problem for context, the part to solve, and the chosen
+/// plans of the sub-problems it depends on
+pub fn sub_problem_prompt(problem: &str, sub: &SubProblem, solved: &[PlanSection]) -> String {
+    let mut prompt = format!(
+        "Overall problem:\n{}\n\nPlan only this part of it:\n{}\n{}",
+        problem, sub.title, sub.description
+    );
+    let deps: Vec<String> = solved
+        .iter()
+        .filter(|s| sub.depends_on.contains(&s.sub_problem.id))
+        .filter_map(|s| {
+            let bundle = serde_json::to_value(s.bundle.as_ref()?).ok()?;
+            Some(format!("{}:\n{}", s.sub_problem.title, summarize_bundle(1, &bundle)))
+        })
+        .collect();
+    if !deps.is_empty() {
+        prompt.push_str(&format!(
+            "\n\nIt builds on these parts, already planned:\n\n{}",
+            deps.join("\n\n")
+        ));
+    }
+    prompt
+}
+
+/// Number every section's steps into one sequence, in section order
+pub fn assemble(sections: Vec<PlanSection>, warnings: Vec<String>, attempts: Vec<Attempt>) -> HierarchicalPlan {
+    let mut steps = Vec::new();
+    for section in &sections {
+        let Some(bundle) = &section.bundle else {
+            continue;
+        };
+        let mut own: Vec<_> = bundle.step_plan.iter().collect();
+        own.sort_by_key(|s| s.order);
+        for step in own {
+            steps.push(PlanStep {
+                order: steps.len() as i32 + 1,
+                sub_problem_id: section.sub_problem.id.clone(),
+                action: step.action.clone(),
+                details: step.details.clone(),
+            });
+        }
+    }
+    HierarchicalPlan {
+        sections,
+        steps,
+        warnings,
+        attempts,
+    }
+}
@@ -0,0 +1,109 @@
+//! Drill-down - expand one idea of a stored bundle into its own sub-plan, stored as a child of that idea
+
+use crate::context::{chat_context, summarize_bundle};
+use crate::orchestrator::{Orchestrator, OrchestratorResult};
+use crate::request::{CompletionRequest, GenerationParams};
+use crate::storage::{message_bundles, IdeaRef, Storage, UsageScope};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// What a drill-down stored and produced
+pub struct DrillDown {
+    /// The user turn recording which idea was expanded
+    pub prompt_message_id: String,
+    /// The assistant turn holding the sub-plan bundles; its `parent` is the expanded idea
+    pub message_id: String,
+    pub content: String,
+    pub result: OrchestratorResult,
+}
+
+/// The idea at `target` and the bundle it belongs to, as stored JSON
+pub fn find_idea(storage: &Storage, target: &IdeaRef) -> Result<(String, Value, Value), String> {
+    let message = storage
+        .get_message(&target.message_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Message not found: {}", target.message_id))?;
+    let bundles: Vec<Value> = message
+        .idea_bundles_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    let bundle = bundles
+        .into_iter()
+        .find(|b| b["id"] == target.bundle_id.as_str())
+        .ok_or_else(|| format!("Bundle {} not found in message {}", target.bundle_id, target.message_id))?;
+    let idea = bundle["ideas"]
+        .get(target.idea_index)
+        .cloned()
+        .ok_or_else(|| format!("Bundle {} has no idea {}", target.bundle_id, target.idea_index))?;
+    Ok((message.chat_id, bundle, idea))
+}
+
+/// Focused prompt: the idea itself, then its parent bundle as constraints to stay within
+pub fn drill_down_prompt(idea: &Value, bundle: &Value) -> String {
+    let mut prompt = format!(
+        "Expand this idea into a detailed sub-plan:\n\n{}\n{}",
+        idea["title"].as_str().unwrap_or_default(),
+        idea["description"].as_str().unwrap_or_default()
+    );
+    if let Some(rationale) = idea["rationale"].as_str() {
+        prompt.push_str(&format!("\nRationale: {}", rationale));
+    }
+    prompt.push_str(&format!(
+        "\n\nIt comes from this plan. Keep to its dependencies, risks and effort unless the sub-plan explains why not:\n{}",
+        summarize_bundle(1, bundle)
+    ));
+    prompt.push_str(
+        "\n\nReturn ideas for carrying out this one idea, a concrete step plan, its own risks, dependencies, effort and next actions.",
+    );
+    prompt
+}
+
+/// Expand the idea at `target`: run the orchestrator on a focused prompt with the chat as context,
+/// then store the prompt and the resulting bundles in the same chat, linked to the idea, and the
+/// run's usage against the new assistant message
+pub async fn drill_down(
+    storage: &Storage,
+    orchestrator: &Orchestrator,
+    target: &IdeaRef,
+    system_prompt: &str,
+    params: GenerationParams,
+    rubric: Option<&[f64; 6]>,
+    context_tokens: usize,
+) -> Result<DrillDown, String> {
+    let (chat_id, bundle, idea) = find_idea(storage, target)?;
+    let history = chat_context(storage, &chat_id, context_tokens)?;
+    let request = CompletionRequest::new(system_prompt, &drill_down_prompt(&idea, &bundle))
+        .with_history(history)
+        .with_params(params);
+
+    let prompt_message_id = Uuid::new_v4().to_string();
+    let label = format!("Drill down: {}", idea["title"].as_str().unwrap_or_default());
+    storage
+        .insert_child_message(&prompt_message_id, &chat_id, "user", &label, None, Some(target))
+        .map_err(|e| e.to_string())?;
+
+    let result = orchestrator.run(&request, rubric).await;
+
+    let message_id = Uuid::new_v4().to_string();
+    let content = result.summary();
+    let bundles_json = message_bundles(&result.bundles).to_string();
+    storage
+        .insert_child_message(&message_id, &chat_id, "assistant", &content, Some(&bundles_json), Some(target))
+        .map_err(|e| e.to_string())?;
+    let scope = UsageScope {
+        chat_id: Some(chat_id),
+        message_id: Some(message_id.clone()),
+        ..Default::default()
+    };
+    storage
+        .record_usage(&result.attempts, &scope)
+        .map_err(|e| e.to_string())?;
+
+    Ok(DrillDown {
+        prompt_message_id,
+        message_id,
+        content,
+        result,
+    })
+}
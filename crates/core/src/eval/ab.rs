@@ -0,0 +1,309 @@
+//! A/B comparison of recipes or models - N runs per arm, bootstrap CIs, paired significance tests
+
+use super::stats::{bootstrap_mean_ci, holm_adjust, mean, sign_flip_p_value, SplitMix64};
+use super::{load_recipe_and_problems, run_problems, EvalReport};
+use crate::adapters::{available_adapters, target_adapter, ProviderAdapter};
+use crate::schema::ScoreCard;
+use crate::storage::{chrono_utc_now, EvalComparisonRow, Storage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const DIMENSIONS: [&str; 7] = ["novelty", "feasibility", "cost", "time", "risk", "clarity", "total"];
+
+/// One side of a comparison: a recipe, optionally pinned to a single provider and model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbArm {
+    pub recipe_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl AbArm {
+    /// `target` is `provider` or `provider:model`; without it the arm uses every provider with a key
+    pub fn new(recipe_id: &str, target: Option<&str>) -> Self {
+        let (provider, model) = match target.map(|t| t.split_once(':').unwrap_or((t, ""))) {
+            Some((p, m)) => (Some(p.to_string()), (!m.is_empty()).then(|| m.to_string())),
+            None => (None, None),
+        };
+        Self {
+            recipe_id: recipe_id.to_string(),
+            provider,
+            model,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match (&self.provider, &self.model) {
+            (Some(p), Some(m)) => format!("{} @ {}:{}", self.recipe_id, p, m),
+            (Some(p), None) => format!("{} @ {}", self.recipe_id, p),
+            _ => self.recipe_id.clone(),
+        }
+    }
+
+    pub(super) fn adapters(&self) -> Result<Vec<Arc<dyn ProviderAdapter>>, String> {
+        match &self.provider {
+            Some(provider) => Ok(vec![target_adapter(provider, self.model.as_deref())?]),
+            None => available_adapters(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbOptions {
+    /// Runs of every problem per arm
+    pub repeats: u32,
+    /// Significance level for CIs and tests
+    pub alpha: f64,
+    pub bootstrap_iterations: usize,
+    pub seed: u64,
+}
+
+impl Default for AbOptions {
+    fn default() -> Self {
+        Self {
+            repeats: 3,
+            alpha: 0.05,
+            bootstrap_iterations: 2000,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    BBetter,
+    ABetter,
+    NoDifference,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimensionComparison {
+    pub dimension: String,
+    pub mean_a: f64,
+    pub ci_a: [f64; 2],
+    pub mean_b: f64,
+    pub ci_b: [f64; 2],
+    /// Mean of per-pair differences (B - A)
+    pub mean_diff: f64,
+    pub ci_diff: [f64; 2],
+    pub p_value: f64,
+    /// Holm-adjusted across all dimensions
+    pub p_adjusted: f64,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbComparison {
+    pub id: String,
+    pub arm_a: AbArm,
+    pub arm_b: AbArm,
+    pub options: AbOptions,
+    pub run_id_a: String,
+    pub run_id_b: String,
+    /// Problems scored in both arms
+    pub paired_problems: Vec<String>,
+    /// (problem, repeat) units scored in both arms - the pairing unit for the tests
+    #[serde(default)]
+    pub pairs: usize,
+    pub dimensions: Vec<DimensionComparison>,
+    /// E.g. too few pairs for any dimension to reach significance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// True when B is significantly better on the weighted total
+    pub b_beats_a: bool,
+    pub created_at: String,
+}
+
+/// Run both arms `options.repeats` times over the problems, compare them and store the comparison
+pub fn run_ab(
+    db_path: impl AsRef<Path>,
+    arm_a: &AbArm,
+    arm_b: &AbArm,
+    problem_ids: &[String],
+    options: &AbOptions,
+) -> Result<AbComparison, String> {
+    let storage = Storage::new(db_path).map_err(|e| e.to_string())?;
+    let (recipe_a, problems) = load_recipe_and_problems(&storage, &arm_a.recipe_id, problem_ids)?;
+    let (recipe_b, _) = load_recipe_and_problems(&storage, &arm_b.recipe_id, problem_ids)?;
+    let adapters_a = arm_a.adapters()?;
+    let adapters_b = arm_b.adapters()?;
+    if adapters_a.is_empty() || adapters_b.is_empty() {
+        return Err("No providers available. Set OPENAI_API_KEY, ANTHROPIC_API_KEY or GEMINI_API_KEY.".to_string());
+    }
+
+    let run_id_a = Uuid::new_v4().to_string();
+    let run_id_b = Uuid::new_v4().to_string();
+    // One entry per repeat
+    let mut reports_a = Vec::new();
+    let mut reports_b = Vec::new();
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        // Interleave arms so provider-side drift during the run hits both equally
+        for _ in 0..options.repeats.max(1) {
+            let a = run_problems(&storage, &recipe_a, &problems, adapters_a.clone(), &recipe_a.id, &run_id_a).await?;
+            reports_a.push(a.reports);
+            let b = run_problems(&storage, &recipe_b, &problems, adapters_b.clone(), &recipe_a.id, &run_id_b).await?;
+            reports_b.push(b.reports);
+        }
+        Ok::<_, String>(())
+    })?;
+
+    let mut comparison = compare_samples(arm_a, arm_b, &reports_a, &reports_b, options);
+    comparison.run_id_a = run_id_a;
+    comparison.run_id_b = run_id_b;
+    save_comparison(&storage, &comparison)?;
+    Ok(comparison)
+}
+
+/// Pair the two arms by problem and repeat (`reports_a[i]` is repeat `i` of arm A), averaging the
+/// bundles of one run, and test every score dimension
+pub fn compare_samples(
+    arm_a: &AbArm,
+    arm_b: &AbArm,
+    reports_a: &[Vec<EvalReport>],
+    reports_b: &[Vec<EvalReport>],
+    options: &AbOptions,
+) -> AbComparison {
+    let per_pair_a = per_pair_means(reports_a);
+    let per_pair_b = per_pair_means(reports_b);
+    let paired: Vec<&(String, usize)> = per_pair_a.keys().filter(|key| per_pair_b.contains_key(*key)).collect();
+    let mut paired_problems: Vec<String> = paired.iter().map(|(id, _)| id.clone()).collect();
+    paired_problems.dedup();
+
+    let mut rng = SplitMix64::new(options.seed);
+    let mut dimensions: Vec<DimensionComparison> = DIMENSIONS
+        .iter()
+        .enumerate()
+        .map(|(d, name)| {
+            let a: Vec<f64> = paired.iter().map(|key| per_pair_a[*key][d]).collect();
+            let b: Vec<f64> = paired.iter().map(|key| per_pair_b[*key][d]).collect();
+            let diffs: Vec<f64> = a.iter().zip(&b).map(|(x, y)| y - x).collect();
+            let ci_a = bootstrap_mean_ci(&a, options.bootstrap_iterations, options.alpha, &mut rng);
+            let ci_b = bootstrap_mean_ci(&b, options.bootstrap_iterations, options.alpha, &mut rng);
+            let ci_diff = bootstrap_mean_ci(&diffs, options.bootstrap_iterations, options.alpha, &mut rng);
+            DimensionComparison {
+                dimension: name.to_string(),
+                mean_a: mean(&a),
+                ci_a: [ci_a.0, ci_a.1],
+                mean_b: mean(&b),
+                ci_b: [ci_b.0, ci_b.1],
+                mean_diff: mean(&diffs),
+                ci_diff: [ci_diff.0, ci_diff.1],
+                p_value: sign_flip_p_value(&diffs, options.bootstrap_iterations, &mut rng),
+                p_adjusted: 1.0,
+                verdict: Verdict::NoDifference,
+            }
+        })
+        .collect();
+
+    let adjusted = holm_adjust(&dimensions.iter().map(|d| d.p_value).collect::<Vec<_>>());
+    for (dim, p_adj) in dimensions.iter_mut().zip(adjusted) {
+        dim.p_adjusted = p_adj;
+        // Significant only if the test rejects and the interval agrees on the direction
+        dim.verdict = if p_adj >= options.alpha {
+            Verdict::NoDifference
+        } else if dim.ci_diff[0] > 0.0 {
+            Verdict::BBetter
+        } else if dim.ci_diff[1] < 0.0 {
+            Verdict::ABetter
+        } else {
+            Verdict::NoDifference
+        };
+    }
+
+    let b_beats_a = dimensions
+        .iter()
+        .any(|d| d.dimension == "total" && d.verdict == Verdict::BBetter);
+
+    let warnings = significance_warning(paired.len(), options.alpha).into_iter().collect();
+
+    AbComparison {
+        id: Uuid::new_v4().to_string(),
+        arm_a: arm_a.clone(),
+        arm_b: arm_b.clone(),
+        options: options.clone(),
+        run_id_a: String::new(),
+        run_id_b: String::new(),
+        paired_problems,
+        pairs: paired.len(),
+        dimensions,
+        warnings,
+        b_beats_a,
+        created_at: chrono_utc_now(),
+    }
+}
+
+pub fn save_comparison(storage: &Storage, comparison: &AbComparison) -> Result<(), String> {
+    storage
+        .save_eval_comparison(&EvalComparisonRow {
+            id: comparison.id.clone(),
+            label_a: comparison.arm_a.label(),
+            label_b: comparison.arm_b.label(),
+            b_beats_a: comparison.b_beats_a,
+            result_json: serde_json::to_string(comparison).map_err(|e| e.to_string())?,
+            created_at: comparison.created_at.clone(),
+        })
+        .map_err(|e| e.to_string())
+}
+
+pub fn load_comparison(storage: &Storage, id: &str) -> Result<AbComparison, String> {
+    let row = storage
+        .get_eval_comparison(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Comparison not found: {}", id))?;
+    serde_json::from_str(&row.result_json).map_err(|e| e.to_string())
+}
+
+fn card_dimensions(card: &ScoreCard) -> [f64; 7] {
+    [
+        card.novelty,
+        card.feasibility,
+        card.cost,
+        card.time,
+        card.risk,
+        card.clarity,
+        card.total,
+    ]
+}
+
+/// Mean score per (problem, repeat) over the bundles of that run
+fn per_pair_means(repeats: &[Vec<EvalReport>]) -> BTreeMap<(String, usize), [f64; 7]> {
+    let mut sums: BTreeMap<(String, usize), ([f64; 7], usize)> = BTreeMap::new();
+    for (repeat, reports) in repeats.iter().enumerate() {
+        for report in reports {
+            let entry = sums.entry((report.problem_id.clone(), repeat)).or_insert(([0.0; 7], 0));
+            for (sum, value) in entry.0.iter_mut().zip(card_dimensions(&report.score_card)) {
+                *sum += value;
+            }
+            entry.1 += 1;
+        }
+    }
+    sums.into_iter()
+        .map(|(key, (sum, n))| (key, sum.map(|s| s / n as f64)))
+        .collect()
+}
+
+/// With n pairs the sign-flip test cannot go below p = 2 / 2^n, and Holm multiplies the smallest
+/// p-value by the number of dimensions. Warn when that floor is not below `alpha`.
+fn significance_warning(pairs: usize, alpha: f64) -> Option<String> {
+    let floor = |n: usize| DIMENSIONS.len() as f64 * 2f64.powi(1 - n as i32);
+    if pairs > 0 && floor(pairs) < alpha {
+        return None;
+    }
+    let needed = (1..=64).find(|&n| floor(n) < alpha).unwrap_or(64);
+    Some(format!(
+        "{} pairs cannot reach significance at alpha {}: the smallest Holm-adjusted p-value is {:.3}; \
+         at least {} pairs (problems x repeats) are needed",
+        pairs,
+        alpha,
+        floor(pairs).min(1.0),
+        needed
+    ))
+}
@@ -0,0 +1,177 @@
+//! Rule-based assertions - check bundles against hard constraints from problems and recipes
+
+use crate::schema::{AIResponse, IdeaBundle, Severity};
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    /// The bundle text must contain the term (case-insensitive) or match the regex
+    MustMention {
+        pattern: String,
+        #[serde(default)]
+        regex: bool,
+    },
+    /// The bundle text must not contain the term or match the regex
+    MustNotMention {
+        pattern: String,
+        #[serde(default)]
+        regex: bool,
+    },
+    MinSteps {
+        count: usize,
+    },
+    /// Every high-severity risk carries a non-empty mitigation
+    NoUnmitigatedHighRisk,
+    /// A dependency containing `name` (case-insensitive) is listed
+    RequiresDependency {
+        name: String,
+    },
+    /// The raw provider output is a JSON document with the full response shape
+    ValidJson,
+}
+
+impl Assertion {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::MustMention { pattern, regex: false } => format!("mentions \"{}\"", pattern),
+            Self::MustMention { pattern, regex: true } => format!("matches /{}/", pattern),
+            Self::MustNotMention { pattern, regex: false } => format!("does not mention \"{}\"", pattern),
+            Self::MustNotMention { pattern, regex: true } => format!("does not match /{}/", pattern),
+            Self::MinSteps { count } => format!("at least {} steps", count),
+            Self::NoUnmitigatedHighRisk => "no high-severity risk without mitigation".to_string(),
+            Self::RequiresDependency { name } => format!("depends on \"{}\"", name),
+            Self::ValidJson => "valid JSON response".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Parse an `assertions_json` column. Missing or blank means no assertions; malformed JSON or a
+/// regex that does not compile is an error.
+pub fn parse_assertions(json: Option<&str>) -> Result<Vec<Assertion>, String> {
+    let Some(json) = json.filter(|j| !j.trim().is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let assertions: Vec<Assertion> = serde_json::from_str(json).map_err(|e| format!("assertions: {}", e))?;
+    validate_assertions(&assertions)?;
+    Ok(assertions)
+}
+
+/// Reject regex patterns that do not compile, so they fail when saved instead of on every bundle
+pub fn validate_assertions(assertions: &[Assertion]) -> Result<(), String> {
+    for assertion in assertions {
+        if let Assertion::MustMention { pattern, regex: true } | Assertion::MustNotMention { pattern, regex: true } =
+            assertion
+        {
+            find(pattern, true, "").map_err(|e| format!("assertions: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn check_all(assertions: &[Assertion], bundle: &IdeaBundle) -> Vec<AssertionResult> {
+    let text = bundle_text(bundle);
+    assertions.iter().map(|a| check(a, bundle, &text)).collect()
+}
+
+/// Passed and total assertion counts per provider
+pub fn pass_rates<'a>(results: impl IntoIterator<Item = (&'a str, &'a [AssertionResult])>) -> BTreeMap<String, (usize, usize)> {
+    let mut rates: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (provider, checks) in results {
+        let entry = rates.entry(provider.to_string()).or_default();
+        entry.0 += checks.iter().filter(|c| c.passed).count();
+        entry.1 += checks.len();
+    }
+    rates
+}
+
+fn check(assertion: &Assertion, bundle: &IdeaBundle, text: &str) -> AssertionResult {
+    let (passed, detail) = match assertion {
+        Assertion::MustMention { pattern, regex } => match find(pattern, *regex, text) {
+            Ok(Some(_)) => (true, None),
+            Ok(None) => (false, Some("not found".to_string())),
+            Err(e) => (false, Some(e)),
+        },
+        Assertion::MustNotMention { pattern, regex } => match find(pattern, *regex, text) {
+            Ok(Some(found)) => (false, Some(format!("found \"{}\"", found))),
+            Ok(None) => (true, None),
+            Err(e) => (false, Some(e)),
+        },
+        Assertion::MinSteps { count } => {
+            let n = bundle.step_plan.len();
+            (n >= *count, (n < *count).then(|| format!("{} steps", n)))
+        }
+        Assertion::NoUnmitigatedHighRisk => {
+            let unmitigated: Vec<&str> = bundle
+                .risks
+                .iter()
+                .filter(|r| r.severity == Severity::High)
+                .filter(|r| r.mitigation.as_deref().map(str::trim).unwrap_or_default().is_empty())
+                .map(|r| r.description.as_str())
+                .collect();
+            (unmitigated.is_empty(), (!unmitigated.is_empty()).then(|| unmitigated.join("; ")))
+        }
+        Assertion::RequiresDependency { name } => {
+            let needle = name.to_lowercase();
+            let found = bundle.dependencies.iter().any(|d| d.to_lowercase().contains(&needle));
+            (found, (!found).then(|| "not listed".to_string()))
+        }
+        Assertion::ValidJson => match bundle.raw_response.as_deref() {
+            Some(raw) => match serde_json::from_str::<AIResponse>(raw) {
+                Ok(_) => (true, None),
+                Err(e) => (false, Some(e.to_string())),
+            },
+            None => (false, Some("no raw response".to_string())),
+        },
+    };
+    AssertionResult {
+        assertion: assertion.clone(),
+        passed,
+        detail,
+    }
+}
+
+/// Returns the matched text, if any
+fn find(pattern: &str, regex: bool, text: &str) -> Result<Option<String>, String> {
+    if regex {
+        let re = RegexBuilder::new(pattern)
+            .build()
+            .map_err(|e| format!("invalid regex: {}", e))?;
+        return Ok(re.find(text).map(|m| m.as_str().to_string()));
+    }
+    let haystack = text.to_lowercase();
+    Ok(haystack.contains(&pattern.to_lowercase()).then(|| pattern.to_string()))
+}
+
+/// All human-readable bundle content, one field per line
+fn bundle_text(bundle: &IdeaBundle) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for idea in &bundle.ideas {
+        parts.push(&idea.title);
+        parts.push(&idea.description);
+        parts.extend(idea.rationale.as_deref());
+    }
+    for step in &bundle.step_plan {
+        parts.push(&step.action);
+        parts.extend(step.details.as_deref());
+    }
+    for risk in &bundle.risks {
+        parts.push(&risk.description);
+        parts.extend(risk.mitigation.as_deref());
+    }
+    parts.extend(bundle.dependencies.iter().map(String::as_str));
+    parts.push(&bundle.effort.time);
+    parts.extend(bundle.effort.cost.as_deref());
+    parts.extend(bundle.next_actions.iter().map(|a| a.action.as_str()));
+    parts.join("\n")
+}
@@ -0,0 +1,299 @@
+//! Eval datasets - named problem sets, JSONL/CSV import and export, harvesting from chat history
+
+use super::assertions::{parse_assertions, validate_assertions};
+use super::EvalProblem;
+use crate::storage::{DatasetRow, Storage};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+/// CSV columns with a fixed meaning; any other column is a template variable
+const CSV_RESERVED: [&str; 4] = ["id", "content", "tags", "assertions"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    Jsonl,
+    Csv,
+}
+
+impl DatasetFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Look up a dataset by name, creating it if it does not exist yet
+pub fn ensure_dataset(storage: &Storage, name: &str) -> Result<DatasetRow, String> {
+    if let Some(ds) = storage.get_dataset_by_name(name).map_err(|e| e.to_string())? {
+        return Ok(ds);
+    }
+    storage
+        .create_dataset(&Uuid::new_v4().to_string(), name, None)
+        .map_err(|e| e.to_string())?;
+    storage
+        .get_dataset_by_name(name)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Failed to create dataset {}", name))
+}
+
+pub fn load_dataset(storage: &Storage, name: &str) -> Result<Vec<EvalProblem>, String> {
+    let ds = storage
+        .get_dataset_by_name(name)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Dataset not found: {}", name))?;
+    storage
+        .list_dataset_problems(&ds.id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(EvalProblem::try_from)
+        .collect()
+}
+
+/// Import problems from a file into the named dataset. Returns the number of problems stored.
+/// Fails without storing anything if an id repeats in the file or belongs to another dataset.
+pub fn import_dataset(storage: &Storage, name: &str, path: &Path, format: DatasetFormat) -> Result<usize, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let problems = match format {
+        DatasetFormat::Jsonl => parse_jsonl(BufReader::new(file))?,
+        DatasetFormat::Csv => parse_csv(file)?,
+    };
+    check_ids(storage, name, &problems)?;
+    let ds = ensure_dataset(storage, name)?;
+    for mut problem in problems.iter().cloned() {
+        problem.dataset_id = Some(ds.id.clone());
+        storage.save_eval_problem(&problem.to_row()).map_err(|e| e.to_string())?;
+    }
+    Ok(problems.len())
+}
+
+/// Write every problem of the named dataset to a file. Returns the number of problems written.
+pub fn export_dataset(storage: &Storage, name: &str, path: &Path, format: DatasetFormat) -> Result<usize, String> {
+    let problems = load_dataset(storage, name)?;
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match format {
+        DatasetFormat::Jsonl => write_jsonl(BufWriter::new(file), &problems)?,
+        DatasetFormat::Csv => write_csv(file, &problems)?,
+    }
+    Ok(problems.len())
+}
+
+/// What one harvest did, counted in messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HarvestReport {
+    /// New problems added to the dataset
+    pub harvested: usize,
+    /// Already in this dataset from an earlier harvest; left as they are, edits included
+    pub existing: usize,
+    /// Already harvested into another dataset, which keeps them
+    pub elsewhere: usize,
+}
+
+/// Copy user messages whose assistant answer got `feedback` (e.g. "helpful") into the named dataset.
+/// Problem IDs are the source message IDs, so harvesting twice does not duplicate problems or
+/// overwrite them. Messages already harvested into another dataset stay there.
+pub fn harvest_from_chats(storage: &Storage, name: &str, feedback: &str) -> Result<HarvestReport, String> {
+    let ds = ensure_dataset(storage, name)?;
+    let existing: BTreeSet<String> = storage
+        .list_dataset_problems(&ds.id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|p| p.id)
+        .collect();
+    let mut report = HarvestReport::default();
+    for chat in storage.list_chats().map_err(|e| e.to_string())? {
+        let messages = storage.get_chat_messages(&chat.id).map_err(|e| e.to_string())?;
+        let mut last_user = None;
+        for msg in &messages {
+            if msg.role == "user" {
+                last_user = Some(msg);
+                continue;
+            }
+            if msg.feedback.as_deref() != Some(feedback) {
+                continue;
+            }
+            let Some(question) = last_user.take() else {
+                continue;
+            };
+            if existing.contains(&question.id) {
+                report.existing += 1;
+                continue;
+            }
+            if !taken_elsewhere(storage, Some(&ds.id), std::slice::from_ref(&question.id))?.is_empty() {
+                report.elsewhere += 1;
+                continue;
+            }
+            let mut tags = vec!["harvested".to_string(), feedback.to_string()];
+            if let Some(template) = &chat.template_id {
+                tags.push(format!("template:{}", template));
+            }
+            let problem = EvalProblem {
+                id: question.id.clone(),
+                content: question.content.clone(),
+                dataset_id: Some(ds.id.clone()),
+                variables: BTreeMap::new(),
+                tags,
+                assertions: Vec::new(),
+            };
+            storage.save_eval_problem(&problem.to_row()).map_err(|e| e.to_string())?;
+            report.harvested += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// Problem ids are global and saving one under another dataset would move it there: refuse ids that
+/// repeat within the import or are already stored outside the named dataset
+fn check_ids(storage: &Storage, name: &str, problems: &[EvalProblem]) -> Result<(), String> {
+    let mut seen = BTreeSet::new();
+    let repeated: Vec<&str> = problems
+        .iter()
+        .map(|p| p.id.as_str())
+        .filter(|id| !seen.insert(*id))
+        .collect();
+    if !repeated.is_empty() {
+        return Err(format!("Problem ids repeat in the file: {}", repeated.join(", ")));
+    }
+    let dataset_id = storage.get_dataset_by_name(name).map_err(|e| e.to_string())?.map(|ds| ds.id);
+    let ids: Vec<String> = problems.iter().map(|p| p.id.clone()).collect();
+    let taken = taken_elsewhere(storage, dataset_id.as_deref(), &ids)?;
+    if !taken.is_empty() {
+        return Err(format!(
+            "Problem ids already used outside dataset {}: {}",
+            name,
+            taken.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// The ids among `ids` stored with no dataset or a dataset other than `dataset_id`
+fn taken_elsewhere(storage: &Storage, dataset_id: Option<&str>, ids: &[String]) -> Result<Vec<String>, String> {
+    Ok(storage
+        .get_eval_problems(ids)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|row| dataset_id.is_none() || row.dataset_id.as_deref() != dataset_id)
+        .map(|row| row.id)
+        .collect())
+}
+
+/// One JSON object per line: `{"id"?, "content", "variables"?, "tags"?, "assertions"?}`. Blank lines are skipped.
+pub fn parse_jsonl(reader: impl BufRead) -> Result<Vec<EvalProblem>, String> {
+    let mut problems = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: serde_json::Value =
+            serde_json::from_str(&line).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        if value.get("id").is_none() {
+            value["id"] = serde_json::Value::String(Uuid::new_v4().to_string());
+        }
+        let problem: EvalProblem = serde_json::from_value(value).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        validate_assertions(&problem.assertions).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        problems.push(problem);
+    }
+    Ok(problems)
+}
+
+pub fn write_jsonl(mut writer: impl Write, problems: &[EvalProblem]) -> Result<(), String> {
+    for problem in problems {
+        let mut value = serde_json::to_value(problem).map_err(|e| e.to_string())?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.remove("dataset_id");
+        }
+        writeln!(writer, "{}", value).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Header row required. `content` is mandatory, `id` optional, `tags` is `;`-separated,
+/// `assertions` is a JSON array, and every other column becomes a template variable.
+pub fn parse_csv(reader: impl Read) -> Result<Vec<EvalProblem>, String> {
+    let mut rdr = csv::Reader::from_reader(reader);
+    let headers = rdr.headers().map_err(|e| e.to_string())?.clone();
+    let content_idx = headers
+        .iter()
+        .position(|h| h == "content")
+        .ok_or("CSV is missing a `content` column")?;
+    let id_idx = headers.iter().position(|h| h == "id");
+    let tags_idx = headers.iter().position(|h| h == "tags");
+    let assertions_idx = headers.iter().position(|h| h == "assertions");
+
+    let mut problems = Vec::new();
+    for record in rdr.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let id = id_idx
+            .and_then(|i| record.get(i))
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let tags = tags_idx
+            .and_then(|i| record.get(i))
+            .map(|s| s.split(';').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        let assertions =
+            parse_assertions(assertions_idx.and_then(|i| record.get(i))).map_err(|e| format!("{}: {}", id, e))?;
+        let variables = headers
+            .iter()
+            .zip(record.iter())
+            .filter(|(h, v)| !CSV_RESERVED.contains(h) && !v.is_empty())
+            .map(|(h, v)| (h.to_string(), v.to_string()))
+            .collect();
+        problems.push(EvalProblem {
+            id,
+            content: record.get(content_idx).unwrap_or_default().to_string(),
+            dataset_id: None,
+            variables,
+            tags,
+            assertions,
+        });
+    }
+    Ok(problems)
+}
+
+pub fn write_csv(writer: impl Write, problems: &[EvalProblem]) -> Result<(), String> {
+    let variable_names: BTreeSet<&str> = problems
+        .iter()
+        .flat_map(|p| p.variables.keys().map(String::as_str))
+        .collect();
+    let mut wtr = csv::Writer::from_writer(writer);
+    let header: Vec<&str> = CSV_RESERVED.iter().copied().chain(variable_names.iter().copied()).collect();
+    wtr.write_record(&header).map_err(|e| e.to_string())?;
+    for problem in problems {
+        let tags = problem.tags.join(";");
+        let assertions = if problem.assertions.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&problem.assertions).map_err(|e| e.to_string())?
+        };
+        let mut record = vec![
+            problem.id.as_str(),
+            problem.content.as_str(),
+            tags.as_str(),
+            assertions.as_str(),
+        ];
+        record.extend(
+            variable_names
+                .iter()
+                .map(|name| problem.variables.get(*name).map(String::as_str).unwrap_or_default()),
+        );
+        wtr.write_record(&record).map_err(|e| e.to_string())?;
+    }
+    wtr.flush().map_err(|e| e.to_string())
+}
@@ -0,0 +1,403 @@
+//! Local eval runner - replays saved problems against prompt recipes, reports deltas
+
+pub mod ab;
+pub mod assertions;
+pub mod dataset;
+pub mod report;
+pub mod stats;
+pub mod tournament;
+
+use self::assertions::{check_all, parse_assertions, Assertion, AssertionResult};
+use crate::adapters::{available_adapters, ProviderAdapter};
+use crate::orchestrator::Orchestrator;
+use crate::ranker::{parse_rubric, Ranker};
+use crate::request::{CompletionRequest, GenerationParams};
+use crate::schema::ScoreCard;
+use crate::storage::{
+    chrono_utc_now, EvalFailureRow, EvalProblemRow, EvalResultRow, RecipeRow, Storage, UsageScope,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalProblem {
+    pub id: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset_id: Option<String>,
+    /// Values for `{{name}}` placeholders in the recipe's user prompt template
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Checked against every bundle for this problem, in addition to the recipe's assertions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Assertion>,
+}
+
+impl TryFrom<EvalProblemRow> for EvalProblem {
+    type Error = String;
+
+    fn try_from(row: EvalProblemRow) -> Result<Self, String> {
+        let assertions =
+            parse_assertions(row.assertions_json.as_deref()).map_err(|e| format!("problem {}: {}", row.id, e))?;
+        Ok(Self {
+            id: row.id,
+            content: row.content,
+            dataset_id: row.dataset_id,
+            variables: row
+                .variables_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            tags: row
+                .tags_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            assertions,
+        })
+    }
+}
+
+impl EvalProblem {
+    pub fn to_row(&self) -> EvalProblemRow {
+        EvalProblemRow {
+            id: self.id.clone(),
+            dataset_id: self.dataset_id.clone(),
+            content: self.content.clone(),
+            variables_json: (!self.variables.is_empty())
+                .then(|| serde_json::to_string(&self.variables).unwrap_or_default()),
+            tags_json: (!self.tags.is_empty()).then(|| serde_json::to_string(&self.tags).unwrap_or_default()),
+            assertions_json: (!self.assertions.is_empty())
+                .then(|| serde_json::to_string(&self.assertions).unwrap_or_default()),
+            created_at: chrono_utc_now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub recipe_id: String,
+    pub problem_id: String,
+    pub bundle_id: String,
+    pub provider: String,
+    pub model: String,
+    pub score_card: ScoreCard,
+    pub delta: Option<DeltaScore>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<AssertionResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaScore {
+    pub novelty: f64,
+    pub feasibility: f64,
+    pub cost: f64,
+    pub time: f64,
+    pub risk: f64,
+    pub clarity: f64,
+    #[serde(default)]
+    pub total: f64,
+}
+
+impl DeltaScore {
+    pub fn between(baseline: &ScoreCard, current: &ScoreCard) -> Self {
+        Self {
+            novelty: current.novelty - baseline.novelty,
+            feasibility: current.feasibility - baseline.feasibility,
+            cost: current.cost - baseline.cost,
+            time: current.time - baseline.time,
+            risk: current.risk - baseline.risk,
+            clarity: current.clarity - baseline.clarity,
+            total: current.total - baseline.total,
+        }
+    }
+}
+
+impl EvalReport {
+    fn from_row(row: EvalResultRow) -> Option<Self> {
+        Some(Self {
+            score_card: serde_json::from_str(&row.score_card_json).ok()?,
+            delta: row.delta_json.and_then(|json| serde_json::from_str(&json).ok()),
+            assertions: row
+                .assertions_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            recipe_id: row.recipe_id,
+            problem_id: row.problem_id,
+            bundle_id: row.bundle_id,
+            provider: row.provider,
+            model: row.model,
+        })
+    }
+}
+
+/// A provider that failed on one problem during a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalFailure {
+    pub problem_id: String,
+    pub provider: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalRun {
+    pub run_id: String,
+    pub reports: Vec<EvalReport>,
+    pub failures: Vec<EvalFailure>,
+}
+
+/// Run eval: load problems and recipe from storage, run the orchestrator, score and persist every bundle,
+/// near-duplicates included. Deltas compare against the previous run of each problem with `baseline_recipe_id` (default: the same recipe).
+pub fn run_eval(
+    db_path: impl AsRef<Path>,
+    recipe_id: &str,
+    problem_ids: &[String],
+    baseline_recipe_id: Option<&str>,
+) -> Result<EvalRun, String> {
+    let storage = Storage::new(db_path).map_err(|e| e.to_string())?;
+    let (recipe, problems) = load_recipe_and_problems(&storage, recipe_id, problem_ids)?;
+
+    let adapters = available_adapters()?;
+    if adapters.is_empty() {
+        return Err("No providers available. Set OPENAI_API_KEY, ANTHROPIC_API_KEY or GEMINI_API_KEY.".to_string());
+    }
+
+    let run_id = Uuid::new_v4().to_string();
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(run_problems(
+        &storage,
+        &recipe,
+        &problems,
+        adapters,
+        baseline_recipe_id.unwrap_or(recipe_id),
+        &run_id,
+    ))
+}
+
+fn load_recipe_and_problems(
+    storage: &Storage,
+    recipe_id: &str,
+    problem_ids: &[String],
+) -> Result<(RecipeRow, Vec<EvalProblem>), String> {
+    let recipe = storage
+        .get_recipe(recipe_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Recipe not found: {}", recipe_id))?;
+
+    let problems: Vec<EvalProblem> = storage
+        .get_eval_problems(problem_ids)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(EvalProblem::try_from)
+        .collect::<Result<_, _>>()?;
+    if let Some(missing) = problem_ids.iter().find(|id| !problems.iter().any(|p| &p.id == *id)) {
+        return Err(format!("Problem not found: {}", missing));
+    }
+    Ok((recipe, problems))
+}
+
+async fn run_problems(
+    storage: &Storage,
+    recipe: &RecipeRow,
+    problems: &[EvalProblem],
+    adapters: Vec<Arc<dyn ProviderAdapter>>,
+    baseline_recipe_id: &str,
+    run_id: &str,
+) -> Result<EvalRun, String> {
+    let rubric = parse_rubric(&recipe.rubric_json);
+    let ranker = Ranker::new(rubric.as_ref());
+    let orchestrator = Orchestrator::new(adapters);
+    let system_prompt = build_system_prompt(recipe);
+    let recipe_assertions =
+        parse_assertions(recipe.assertions_json.as_deref()).map_err(|e| format!("recipe {}: {}", recipe.id, e))?;
+    let params = GenerationParams::from_json(recipe.params_json.as_deref())?;
+
+    let mut reports = Vec::new();
+    let mut failures = Vec::new();
+
+    for problem in problems {
+        let user_prompt = render_user_prompt(&recipe.user_prompt_template, problem);
+        let request = CompletionRequest::new(&system_prompt, &user_prompt).with_params(params.clone());
+        let result = orchestrator.run(&request, rubric.as_ref()).await;
+        storage
+            .record_usage(
+                &result.attempts,
+                &UsageScope {
+                    recipe_id: Some(recipe.id.clone()),
+                    run_id: Some(run_id.to_string()),
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| e.to_string())?;
+
+        for (provider, error) in &result.errors {
+            storage
+                .insert_eval_failure(&EvalFailureRow {
+                    id: Uuid::new_v4().to_string(),
+                    run_id: run_id.to_string(),
+                    recipe_id: recipe.id.clone(),
+                    problem_id: problem.id.clone(),
+                    provider: provider.clone(),
+                    error: error.clone(),
+                    created_at: chrono_utc_now(),
+                })
+                .map_err(|e| e.to_string())?;
+            failures.push(EvalFailure {
+                problem_id: problem.id.clone(),
+                provider: provider.clone(),
+                error: error.clone(),
+            });
+        }
+
+        let assertions: Vec<Assertion> = recipe_assertions.iter().chain(&problem.assertions).cloned().collect();
+        // Score every target, including bundles ranking merged away as near-duplicates of another
+        for bundle in result.all_bundles() {
+            let score_card = ranker.score_bundle(bundle);
+            let checks = check_all(&assertions, bundle);
+            let delta = storage
+                .latest_eval_result(baseline_recipe_id, &problem.id, &bundle.provider, &bundle.model, run_id)
+                .map_err(|e| e.to_string())?
+                .and_then(|row| serde_json::from_str::<ScoreCard>(&row.score_card_json).ok())
+                .map(|baseline| DeltaScore::between(&baseline, &score_card));
+
+            storage
+                .insert_eval_result(&EvalResultRow {
+                    id: Uuid::new_v4().to_string(),
+                    run_id: run_id.to_string(),
+                    recipe_id: recipe.id.clone(),
+                    problem_id: problem.id.clone(),
+                    bundle_id: bundle.id.clone(),
+                    provider: bundle.provider.clone(),
+                    model: bundle.model.clone(),
+                    score_card_json: serde_json::to_string(&score_card).map_err(|e| e.to_string())?,
+                    delta_json: delta.as_ref().and_then(|d| serde_json::to_string(d).ok()),
+                    assertions_json: (!checks.is_empty()).then(|| serde_json::to_string(&checks).unwrap_or_default()),
+                    params_json: bundle.params.as_ref().and_then(|p| serde_json::to_string(p).ok()),
+                    created_at: chrono_utc_now(),
+                })
+                .map_err(|e| e.to_string())?;
+
+            reports.push(EvalReport {
+                recipe_id: recipe.id.clone(),
+                problem_id: problem.id.clone(),
+                bundle_id: bundle.id.clone(),
+                provider: bundle.provider.clone(),
+                model: bundle.model.clone(),
+                score_card,
+                delta,
+                assertions: checks,
+            });
+        }
+    }
+
+    Ok(EvalRun {
+        run_id: run_id.to_string(),
+        reports,
+        failures,
+    })
+}
+
+/// Load a stored run with its provider failures
+pub fn load_run(storage: &Storage, run_id: &str) -> Result<EvalRun, String> {
+    let rows = storage.get_eval_run(run_id).map_err(|e| e.to_string())?;
+    let failures = storage.get_eval_failures(run_id).map_err(|e| e.to_string())?;
+    if rows.is_empty() && failures.is_empty() {
+        return Err(format!("Eval run not found: {}", run_id));
+    }
+    Ok(EvalRun {
+        run_id: run_id.to_string(),
+        reports: rows.into_iter().filter_map(EvalReport::from_row).collect(),
+        failures: failures
+            .into_iter()
+            .map(|f| EvalFailure {
+                problem_id: f.problem_id,
+                provider: f.provider,
+                error: f.error,
+            })
+            .collect(),
+    })
+}
+
+/// Latest stored run for a recipe
+pub fn latest_run_id(storage: &Storage, recipe_id: &str) -> Result<String, String> {
+    storage
+        .list_eval_runs(Some(recipe_id))
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .map(|r| r.run_id)
+        .ok_or_else(|| format!("No eval runs for recipe {}", recipe_id))
+}
+
+/// Pair the results of two stored runs by problem, provider and model.
+/// Reports come from `candidate_run_id`, with deltas measured against `baseline_run_id`.
+pub fn compare_runs(storage: &Storage, baseline_run_id: &str, candidate_run_id: &str) -> Result<EvalRun, String> {
+    let baseline = load_run(storage, baseline_run_id)?;
+    let candidate = load_run(storage, candidate_run_id)?;
+    let reports = candidate
+        .reports
+        .into_iter()
+        .map(|mut report| {
+            report.delta = baseline
+                .reports
+                .iter()
+                .find(|b| b.problem_id == report.problem_id && b.provider == report.provider && b.model == report.model)
+                .map(|b| DeltaScore::between(&b.score_card, &report.score_card));
+            report
+        })
+        .collect();
+    Ok(EvalRun {
+        run_id: candidate_run_id.to_string(),
+        reports,
+        failures: candidate.failures,
+    })
+}
+
+/// Percentage change of the summed total score across reports that have a baseline.
+/// Negative means the run scored worse than its baseline; None if nothing was comparable.
+pub fn total_change_pct(reports: &[EvalReport]) -> Option<f64> {
+    let (current, baseline) = reports
+        .iter()
+        .filter_map(|r| r.delta.as_ref().map(|d| (r.score_card.total, r.score_card.total - d.total)))
+        .fold((0.0, 0.0), |(c, b), (rc, rb)| (c + rc, b + rb));
+    if baseline <= 0.0 {
+        return None;
+    }
+    Some((current - baseline) / baseline * 100.0)
+}
+
+/// Assertion pass counts per provider across the run's reports
+pub fn assertion_pass_rates(reports: &[EvalReport]) -> std::collections::BTreeMap<String, (usize, usize)> {
+    assertions::pass_rates(reports.iter().map(|r| (r.provider.as_str(), r.assertions.as_slice())))
+}
+
+fn build_system_prompt(recipe: &RecipeRow) -> String {
+    let examples: Vec<String> = recipe
+        .few_shot_examples_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    if examples.is_empty() {
+        return recipe.system_prompt.clone();
+    }
+    format!("{}\n\nExamples:\n{}", recipe.system_prompt, examples.join("\n"))
+}
+
+/// Fill `{{name}}` placeholders from the problem's variables and `{{problem}}` with its text.
+/// If the template has no `{{problem}}` slot the problem text is appended.
+fn render_user_prompt(template: &str, problem: &EvalProblem) -> String {
+    let mut rendered = template.to_string();
+    for (name, value) in &problem.variables {
+        rendered = rendered.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    if rendered.contains("{{problem}}") {
+        rendered.replace("{{problem}}", &problem.content)
+    } else if rendered.trim().is_empty() {
+        problem.content.clone()
+    } else {
+        format!("{}\n\n{}", rendered, problem.content)
+    }
+}
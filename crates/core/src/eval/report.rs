@@ -0,0 +1,559 @@
+//! Eval report rendering - terminal table, Markdown, JSON, JUnit XML and standalone HTML
+
+use super::ab::{AbComparison, Verdict};
+use super::tournament::Rating;
+use super::{assertion_pass_rates, total_change_pct, EvalReport, EvalRun};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Table,
+    Markdown,
+    Json,
+    Junit,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "table" => Some(Self::Table),
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "junit" | "xml" => Some(Self::Junit),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+}
+
+const HEADERS: [&str; 11] = [
+    "problem", "provider/model", "novelty", "feasibility", "cost", "time", "risk", "clarity", "total", "Δ total",
+    "assertions",
+];
+
+/// Render a run. `fail_below_pct` marks reports whose total dropped by more than that percentage
+/// as failures (JUnit) or regressions (other formats). Failed assertions are always failures.
+pub fn render(run: &EvalRun, title: &str, format: ReportFormat, fail_below_pct: Option<f64>) -> String {
+    match format {
+        ReportFormat::Table => render_table(run, title, fail_below_pct),
+        ReportFormat::Markdown => render_markdown(run, title, fail_below_pct),
+        ReportFormat::Json => render_json(run, title),
+        ReportFormat::Junit => render_junit(run, title, fail_below_pct),
+        ReportFormat::Html => render_html(run, title, fail_below_pct),
+    }
+}
+
+/// Percentage change of one report's total against its baseline
+pub fn report_change_pct(report: &EvalReport) -> Option<f64> {
+    let delta = report.delta.as_ref()?;
+    let baseline = report.score_card.total - delta.total;
+    (baseline > 0.0).then(|| delta.total / baseline * 100.0)
+}
+
+fn is_regression(report: &EvalReport, fail_below_pct: Option<f64>) -> bool {
+    match (fail_below_pct, report_change_pct(report)) {
+        (Some(limit), Some(pct)) => pct < -limit,
+        _ => false,
+    }
+}
+
+fn cells(report: &EvalReport) -> Vec<String> {
+    let s = &report.score_card;
+    vec![
+        report.problem_id.clone(),
+        format!("{}/{}", report.provider, report.model),
+        format!("{:.2}", s.novelty),
+        format!("{:.2}", s.feasibility),
+        format!("{:.2}", s.cost),
+        format!("{:.2}", s.time),
+        format!("{:.2}", s.risk),
+        format!("{:.2}", s.clarity),
+        format!("{:.2}", s.total),
+        match (&report.delta, report_change_pct(report)) {
+            (Some(d), Some(pct)) => format!("{:+.2} ({:+.1}%)", d.total, pct),
+            (Some(d), None) => format!("{:+.2}", d.total),
+            _ => "-".to_string(),
+        },
+        assertion_cell(report),
+    ]
+}
+
+fn assertion_cell(report: &EvalReport) -> String {
+    if report.assertions.is_empty() {
+        return "-".to_string();
+    }
+    let passed = report.assertions.iter().filter(|a| a.passed).count();
+    let failed: Vec<String> = report
+        .assertions
+        .iter()
+        .filter(|a| !a.passed)
+        .map(|a| a.assertion.describe())
+        .collect();
+    if failed.is_empty() {
+        format!("{}/{}", passed, report.assertions.len())
+    } else {
+        format!("{}/{} failed: {}", passed, report.assertions.len(), failed.join(", "))
+    }
+}
+
+/// Failure lines for a report's failed assertions, with details
+fn assertion_failures(report: &EvalReport) -> Vec<String> {
+    report
+        .assertions
+        .iter()
+        .filter(|a| !a.passed)
+        .map(|a| match &a.detail {
+            Some(detail) => format!("{} ({})", a.assertion.describe(), detail),
+            None => a.assertion.describe(),
+        })
+        .collect()
+}
+
+/// "openai 5/6 (83%), gemini 6/6 (100%)" or None when no assertions ran
+fn pass_rate_line(run: &EvalRun) -> Option<String> {
+    let rates = assertion_pass_rates(&run.reports);
+    let parts: Vec<String> = rates
+        .iter()
+        .filter(|(_, (_, total))| *total > 0)
+        .map(|(provider, (passed, total))| {
+            format!(
+                "{} {}/{} ({:.0}%)",
+                provider,
+                passed,
+                total,
+                *passed as f64 / *total as f64 * 100.0
+            )
+        })
+        .collect();
+    (!parts.is_empty()).then(|| format!("assertions passed: {}", parts.join(", ")))
+}
+
+fn summary_line(run: &EvalRun) -> String {
+    let change = total_change_pct(&run.reports)
+        .map(|pct| format!(", total vs baseline {:+.1}%", pct))
+        .unwrap_or_default();
+    format!(
+        "run {}: {} results, {} failures{}",
+        run.run_id,
+        run.reports.len(),
+        run.failures.len(),
+        change
+    )
+}
+
+fn render_table(run: &EvalRun, title: &str, fail_below_pct: Option<f64>) -> String {
+    let rows: Vec<Vec<String>> = run.reports.iter().map(cells).collect();
+    let lines = text_table(&HEADERS, &rows);
+
+    let mut out = format!("{}\n{}\n", title, summary_line(run));
+    if let Some(rates) = pass_rate_line(run) {
+        out.push_str(&rates);
+        out.push('\n');
+    }
+    out.push('\n');
+    for line in &lines[..2] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    for (report, line) in run.reports.iter().zip(&lines[2..]) {
+        out.push_str(line);
+        if is_regression(report, fail_below_pct) {
+            out.push_str("  REGRESSION");
+        }
+        out.push('\n');
+    }
+    for f in &run.failures {
+        out.push_str(&format!("error: {} {}: {}\n", f.problem_id, f.provider, f.error));
+    }
+    out
+}
+
+fn render_markdown(run: &EvalRun, title: &str, fail_below_pct: Option<f64>) -> String {
+    let mut out = format!("# {}\n\n{}\n\n", title, summary_line(run));
+    if let Some(rates) = pass_rate_line(run) {
+        out.push_str(&format!("{}\n\n", rates));
+    }
+    out.push_str(&format!("| {} |\n", HEADERS.join(" | ")));
+    out.push_str(&format!("|{}\n", " --- |".repeat(HEADERS.len())));
+    for report in &run.reports {
+        let mut row = cells(report);
+        if is_regression(report, fail_below_pct) {
+            row[9] = format!("**{}** ⚠️", row[9]);
+        }
+        let row: Vec<String> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+        out.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    if !run.failures.is_empty() {
+        out.push_str("\n## Failures\n\n");
+        for f in &run.failures {
+            out.push_str(&format!("- `{}` {}: {}\n", f.problem_id, f.provider, f.error));
+        }
+    }
+    out
+}
+
+fn render_json(run: &EvalRun, title: &str) -> String {
+    let doc = serde_json::json!({
+        "title": title,
+        "runId": run.run_id,
+        "totalChangePct": total_change_pct(&run.reports),
+        "assertionPassRates": assertion_pass_rates(&run.reports)
+            .into_iter()
+            .map(|(provider, (passed, total))| (provider, serde_json::json!({ "passed": passed, "total": total })))
+            .collect::<serde_json::Map<_, _>>(),
+        "reports": run.reports,
+        "failures": run.failures,
+    });
+    serde_json::to_string_pretty(&doc).unwrap_or_default()
+}
+
+fn render_junit(run: &EvalRun, title: &str, fail_below_pct: Option<f64>) -> String {
+    let failures = run
+        .reports
+        .iter()
+        .filter(|r| is_regression(r, fail_below_pct) || r.assertions.iter().any(|a| !a.passed))
+        .count();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+        xml_escape(title),
+        run.reports.len() + run.failures.len(),
+        failures,
+        run.failures.len()
+    ));
+    for report in &run.reports {
+        out.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}/{}\">\n",
+            xml_escape(&report.problem_id),
+            xml_escape(&report.provider),
+            xml_escape(&report.model)
+        ));
+        let mut problems = assertion_failures(report);
+        if is_regression(report, fail_below_pct) {
+            problems.insert(
+                0,
+                format!(
+                    "total dropped {:.1}% (total {:.2}, delta {:+.2})",
+                    -report_change_pct(report).unwrap_or_default(),
+                    report.score_card.total,
+                    report.delta.as_ref().map(|d| d.total).unwrap_or_default()
+                ),
+            );
+        }
+        if !problems.is_empty() {
+            out.push_str(&format!(
+                "    <failure message=\"{}\">{}</failure>\n",
+                xml_escape(&problems[0]),
+                xml_escape(&problems.join("\n"))
+            ));
+        }
+        out.push_str(&format!(
+            "    <system-out>{}</system-out>\n  </testcase>\n",
+            xml_escape(&serde_json::to_string(&report.score_card).unwrap_or_default())
+        ));
+    }
+    for f in &run.failures {
+        out.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}\">\n    <error message=\"{}\"/>\n  </testcase>\n",
+            xml_escape(&f.problem_id),
+            xml_escape(&f.provider),
+            xml_escape(&f.error)
+        ));
+    }
+    out.push_str("</testsuite>\n");
+    out
+}
+
+fn render_html(run: &EvalRun, title: &str, fail_below_pct: Option<f64>) -> String {
+    let mut rows = String::new();
+    for report in &run.reports {
+        let class = if is_regression(report, fail_below_pct) || report.assertions.iter().any(|a| !a.passed) {
+            " class=\"regression\""
+        } else {
+            ""
+        };
+        let tds: String = cells(report)
+            .iter()
+            .map(|c| format!("<td>{}</td>", xml_escape(c)))
+            .collect();
+        rows.push_str(&format!("<tr{}>{}</tr>\n", class, tds));
+    }
+    let failures: String = run
+        .failures
+        .iter()
+        .map(|f| {
+            format!(
+                "<li><code>{}</code> {}: {}</li>\n",
+                xml_escape(&f.problem_id),
+                xml_escape(&f.provider),
+                xml_escape(&f.error)
+            )
+        })
+        .collect();
+    let rates = pass_rate_line(run)
+        .map(|r| format!("<p>{}</p>\n", xml_escape(&r)))
+        .unwrap_or_default();
+    let failures = if failures.is_empty() {
+        String::new()
+    } else {
+        format!("<h2>Failures</h2>\n<ul>\n{}</ul>\n", failures)
+    };
+    let body = format!(
+        "<p>{}</p>\n{}{}{}",
+        xml_escape(&summary_line(run)),
+        rates,
+        html_table(&HEADERS, &rows),
+        failures
+    );
+    html_page(title, &body)
+}
+
+const AB_HEADERS: [&str; 6] = ["dimension", "A mean [CI]", "B mean [CI]", "B - A [CI]", "p (Holm)", "verdict"];
+
+/// Render an A/B comparison; one row (or JUnit test case) per score dimension
+pub fn render_comparison(cmp: &AbComparison, format: ReportFormat) -> String {
+    let title = format!("A/B: {} vs {}", cmp.arm_b.label(), cmp.arm_a.label());
+    let mut summary = format!(
+        "{} pairs ({} problems, {} runs per arm), alpha {}; B beats A on total: {}",
+        cmp.pairs,
+        cmp.paired_problems.len(),
+        cmp.options.repeats,
+        cmp.options.alpha,
+        if cmp.b_beats_a { "yes" } else { "no" }
+    );
+    for warning in &cmp.warnings {
+        summary.push_str(&format!("\nWarning: {}", warning));
+    }
+    let rows: Vec<Vec<String>> = cmp
+        .dimensions
+        .iter()
+        .map(|d| {
+            vec![
+                d.dimension.clone(),
+                format!("{:.2} [{:.2}, {:.2}]", d.mean_a, d.ci_a[0], d.ci_a[1]),
+                format!("{:.2} [{:.2}, {:.2}]", d.mean_b, d.ci_b[0], d.ci_b[1]),
+                format!("{:+.2} [{:+.2}, {:+.2}]", d.mean_diff, d.ci_diff[0], d.ci_diff[1]),
+                format!("{:.4} ({:.4})", d.p_value, d.p_adjusted),
+                verdict_label(d.verdict).to_string(),
+            ]
+        })
+        .collect();
+
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(cmp).unwrap_or_default(),
+        ReportFormat::Table => {
+            let mut out = format!("{}\n{}\n\n", title, summary);
+            for line in text_table(&AB_HEADERS, &rows) {
+                out.push_str(&line);
+                out.push('\n');
+            }
+            out
+        }
+        ReportFormat::Markdown => {
+            let mut out = format!("# {}\n\n{}\n\n| {} |\n|{}\n", title, summary, AB_HEADERS.join(" | "), " --- |".repeat(AB_HEADERS.len()));
+            for row in rows {
+                out.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+            out
+        }
+        ReportFormat::Junit => {
+            let failures = cmp.dimensions.iter().filter(|d| d.verdict == Verdict::ABetter).count();
+            let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str(&format!(
+                "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+                xml_escape(&title),
+                cmp.dimensions.len(),
+                failures
+            ));
+            for (d, row) in cmp.dimensions.iter().zip(&rows) {
+                out.push_str(&format!("  <testcase classname=\"ab\" name=\"{}\">\n", xml_escape(&d.dimension)));
+                if d.verdict == Verdict::ABetter {
+                    out.push_str(&format!(
+                        "    <failure message=\"B significantly worse\">{}</failure>\n",
+                        xml_escape(&row[3])
+                    ));
+                }
+                out.push_str("  </testcase>\n");
+            }
+            out.push_str("</testsuite>\n");
+            out
+        }
+        ReportFormat::Html => {
+            let body: String = cmp
+                .dimensions
+                .iter()
+                .zip(&rows)
+                .map(|(d, row)| {
+                    let class = match d.verdict {
+                        Verdict::BBetter => " class=\"better\"",
+                        Verdict::ABetter => " class=\"regression\"",
+                        Verdict::NoDifference => "",
+                    };
+                    let tds: String = row.iter().map(|c| format!("<td>{}</td>", xml_escape(c))).collect();
+                    format!("<tr{}>{}</tr>\n", class, tds)
+                })
+                .collect();
+            let body = format!("<p>{}</p>\n{}", xml_escape(&summary), html_table(&AB_HEADERS, &body));
+            html_page(&title, &body)
+        }
+    }
+}
+
+const LEADERBOARD_HEADERS: [&str; 7] = ["rank", "contestant", "elo", "bradley-terry", "W-L-T", "matches", "win %"];
+
+/// Render the judge leaderboard, best Elo first. JUnit lists one passing test case per contestant.
+pub fn render_leaderboard(ratings: &[Rating], format: ReportFormat) -> String {
+    let title = "Judge leaderboard";
+    let rows: Vec<Vec<String>> = ratings
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let win_pct = if r.matches > 0 {
+                (r.wins as f64 + 0.5 * r.ties as f64) / r.matches as f64 * 100.0
+            } else {
+                0.0
+            };
+            vec![
+                (i + 1).to_string(),
+                r.contestant.clone(),
+                format!("{:.0}", r.elo),
+                format!("{:.0}", r.bradley_terry),
+                format!("{}-{}-{}", r.wins, r.losses, r.ties),
+                r.matches.to_string(),
+                format!("{:.0}%", win_pct),
+            ]
+        })
+        .collect();
+
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(ratings).unwrap_or_default(),
+        ReportFormat::Table => {
+            let mut out = format!("{}\n\n", title);
+            for line in text_table(&LEADERBOARD_HEADERS, &rows) {
+                out.push_str(&line);
+                out.push('\n');
+            }
+            out
+        }
+        ReportFormat::Markdown => {
+            let mut out = format!(
+                "# {}\n\n| {} |\n|{}\n",
+                title,
+                LEADERBOARD_HEADERS.join(" | "),
+                " --- |".repeat(LEADERBOARD_HEADERS.len())
+            );
+            for row in rows {
+                out.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+            out
+        }
+        ReportFormat::Junit => {
+            let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str(&format!(
+                "<testsuite name=\"{}\" tests=\"{}\" failures=\"0\">\n",
+                title,
+                ratings.len()
+            ));
+            for row in &rows {
+                out.push_str(&format!(
+                    "  <testcase classname=\"leaderboard\" name=\"{}\"><system-out>elo {} bt {} {}</system-out></testcase>\n",
+                    xml_escape(&row[1]),
+                    row[2],
+                    row[3],
+                    row[4]
+                ));
+            }
+            out.push_str("</testsuite>\n");
+            out
+        }
+        ReportFormat::Html => {
+            let body: String = rows
+                .iter()
+                .map(|row| {
+                    let tds: String = row.iter().map(|c| format!("<td>{}</td>", xml_escape(c))).collect();
+                    format!("<tr>{}</tr>\n", tds)
+                })
+                .collect();
+            html_page(title, &html_table(&LEADERBOARD_HEADERS, &body))
+        }
+    }
+}
+
+/// Header, rule and one line per row, each column padded to its widest cell
+fn text_table(headers: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<width$}", c, width = *w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![
+        line(&headers.iter().map(|h| h.to_string()).collect::<Vec<_>>()),
+        widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  "),
+    ];
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines
+}
+
+/// `<table>` with `headers` around already rendered `<tr>` rows
+fn html_table(headers: &[&str], rows: &str) -> String {
+    let headers: String = headers.iter().map(|h| format!("<th>{}</th>", h)).collect();
+    format!(
+        "<table>\n<thead><tr>{}</tr></thead>\n<tbody>\n{}</tbody>\n</table>\n",
+        headers, rows
+    )
+}
+
+/// Standalone HTML report: `title` as the heading, then `body`. Rows can be marked
+/// `class="regression"` or `class="better"`.
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #1f2933; }}
+table {{ border-collapse: collapse; width: 100%; font-size: 0.9rem; }}
+th, td {{ border-bottom: 1px solid #e4e7eb; padding: 0.4rem 0.6rem; text-align: left; }}
+th {{ background: #f5f7fa; }}
+tr.regression td {{ background: #fde8e8; }}
+tr.better td {{ background: #e3f9e5; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}</body>
+</html>
+"#,
+        title = xml_escape(title),
+        body = body,
+    )
+}
+
+fn verdict_label(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::BBetter => "B better",
+        Verdict::ABetter => "A better",
+        Verdict::NoDifference => "no significant difference",
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
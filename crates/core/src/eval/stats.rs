@@ -0,0 +1,165 @@
+//! Small statistics toolkit for eval comparisons - bootstrap CIs and paired sign-flip tests
+
+/// Exact enumeration of sign flips up to this many pairs, Monte Carlo above it
+const EXACT_PERMUTATION_MAX: usize = 16;
+
+/// Deterministic SplitMix64 generator, so the same samples and seed give the same intervals
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+pub fn mean(xs: &[f64]) -> f64 {
+    if xs.is_empty() {
+        0.0
+    } else {
+        xs.iter().sum::<f64>() / xs.len() as f64
+    }
+}
+
+/// Percentile bootstrap confidence interval for the mean at level `1 - alpha`
+pub fn bootstrap_mean_ci(xs: &[f64], iterations: usize, alpha: f64, rng: &mut SplitMix64) -> (f64, f64) {
+    if xs.len() < 2 || iterations == 0 {
+        let m = mean(xs);
+        return (m, m);
+    }
+    let mut means: Vec<f64> = (0..iterations)
+        .map(|_| (0..xs.len()).map(|_| xs[rng.below(xs.len())]).sum::<f64>() / xs.len() as f64)
+        .collect();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    (percentile(&means, alpha / 2.0), percentile(&means, 1.0 - alpha / 2.0))
+}
+
+/// Two-sided p-value of a paired sign-flip permutation test on the differences `diffs`.
+/// Under H0 each difference is equally likely to have either sign.
+pub fn sign_flip_p_value(diffs: &[f64], iterations: usize, rng: &mut SplitMix64) -> f64 {
+    let n = diffs.len();
+    if n == 0 {
+        return 1.0;
+    }
+    let observed = mean(diffs).abs();
+    // Tolerance so ties with the observed statistic count as "at least as extreme"
+    let eps = 1e-12;
+
+    if n <= EXACT_PERMUTATION_MAX {
+        let total = 1u32 << n;
+        let extreme = (0..total)
+            .filter(|mask| {
+                let s: f64 = diffs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, d)| if mask & (1 << i) != 0 { -d } else { *d })
+                    .sum();
+                (s / n as f64).abs() >= observed - eps
+            })
+            .count();
+        return extreme as f64 / total as f64;
+    }
+
+    let iterations = iterations.max(1);
+    let extreme = (0..iterations)
+        .filter(|_| {
+            let s: f64 = diffs
+                .iter()
+                .map(|d| if rng.next_u64() & 1 == 1 { -d } else { *d })
+                .sum();
+            (s / n as f64).abs() >= observed - eps
+        })
+        .count();
+    // Add-one smoothing keeps a Monte Carlo p-value from being exactly zero
+    (extreme + 1) as f64 / (iterations + 1) as f64
+}
+
+/// Holm-Bonferroni step-down adjustment, returned in the input order
+pub fn holm_adjust(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| p_values[a].partial_cmp(&p_values[b]).unwrap_or(std::cmp::Ordering::Equal));
+    let mut adjusted = vec![0.0; m];
+    let mut running_max: f64 = 0.0;
+    for (rank, &i) in order.iter().enumerate() {
+        let p = (p_values[i] * (m - rank) as f64).min(1.0);
+        running_max = running_max.max(p);
+        adjusted[i] = running_max;
+    }
+    adjusted
+}
+
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn exact_sign_flip_p_value() {
+        let mut rng = SplitMix64::new(1);
+        // Only all-positive and all-negative reach |mean| 2: 2 of 8 sign patterns
+        assert!(close(sign_flip_p_value(&[1.0, 2.0, 3.0], 0, &mut rng), 0.25));
+        assert!(close(sign_flip_p_value(&[1.0; 5], 0, &mut rng), 2.0 / 32.0));
+        assert!(close(sign_flip_p_value(&[1.0, -1.0], 0, &mut rng), 1.0));
+        assert!(close(sign_flip_p_value(&[], 0, &mut rng), 1.0));
+    }
+
+    #[test]
+    fn holm_adjustment_matches_hand_computed_values() {
+        let raw = [0.01, 0.04, 0.03, 0.005];
+        let adjusted = holm_adjust(&raw);
+        // Sorted: 0.005*4, 0.01*3, 0.03*2, 0.04*1 raised to the running max 0.06
+        let expected = [0.03, 0.06, 0.06, 0.02];
+        for (a, e) in adjusted.iter().zip(expected) {
+            assert!(close(*a, e), "{:?}", adjusted);
+        }
+
+        let mut order: Vec<usize> = (0..raw.len()).collect();
+        order.sort_by(|&a, &b| raw[a].partial_cmp(&raw[b]).unwrap());
+        assert!(order.windows(2).all(|w| adjusted[w[0]] <= adjusted[w[1]]));
+        assert_eq!(holm_adjust(&[0.5, 0.6]), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn percentile_interpolates() {
+        let sorted = [0.0, 10.0, 20.0, 30.0, 40.0];
+        assert!(close(percentile(&sorted, 0.5), 20.0));
+        assert!(close(percentile(&sorted, 0.1), 4.0));
+        assert!(close(percentile(&sorted, 1.0), 40.0));
+    }
+
+    #[test]
+    fn bootstrap_ci_shrinks_as_n_grows() {
+        let mut data = SplitMix64::new(7);
+        let sample: Vec<f64> = (0..1000).map(|_| data.below(100) as f64).collect();
+        let width = |n: usize| {
+            let (lo, hi) = bootstrap_mean_ci(&sample[..n], 2000, 0.05, &mut SplitMix64::new(42));
+            assert!(lo <= mean(&sample[..n]) && mean(&sample[..n]) <= hi);
+            hi - lo
+        };
+        let widths = [width(10), width(100), width(1000)];
+        assert!(widths.windows(2).all(|w| w[1] < w[0]), "{:?}", widths);
+        assert_eq!(width(100), width(100));
+    }
+}
@@ -0,0 +1,421 @@
+//! Pairwise judge tournaments - a judge model picks the better of two bundles, ratings via Elo and Bradley-Terry
+
+use super::ab::AbArm;
+use super::stats::SplitMix64;
+use super::{build_system_prompt, load_recipe_and_problems, render_user_prompt, EvalFailure, EvalProblem};
+use crate::adapters::{target_adapter, ProviderAdapter};
+use crate::json_repair::parse_lenient;
+use crate::orchestrator::{Orchestrator, DEFAULT_TIMEOUT_SECS};
+use crate::ranker::parse_rubric;
+use crate::request::{CompletionRequest, GenerationParams};
+use crate::schema::IdeaBundle;
+use crate::storage::{chrono_utc_now, EvalMatchRow, EvalRatingRow, Storage, UsageScope};
+use crate::usage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+const INITIAL_RATING: f64 = 1500.0;
+const BT_ITERATIONS: usize = 200;
+
+const JUDGE_SYSTEM_PROMPT: &str = "You are an impartial judge comparing two idea bundles written for the same problem. \
+Prefer the bundle that is more useful, feasible, specific and honest about risks. \
+Ignore length, formatting and which bundle is shown first. \
+Respond with JSON only: {\"winner\": \"A\" | \"B\" | \"tie\", \"reason\": \"one or two sentences\"}";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentOptions {
+    /// Seeds the presentation order, so position bias averages out across matches
+    pub seed: u64,
+    pub k_factor: f64,
+}
+
+impl Default for TournamentOptions {
+    fn default() -> Self {
+        Self { seed: 42, k_factor: 32.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    A,
+    B,
+    Tie,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::A => "a",
+            Self::B => "b",
+            Self::Tie => "tie",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "a" => Some(Self::A),
+            "b" => Some(Self::B),
+            "tie" | "draw" => Some(Self::Tie),
+            _ => None,
+        }
+    }
+
+    /// Score for contestant A: 1 win, 0.5 tie, 0 loss
+    fn score_a(self) -> f64 {
+        match self {
+            Self::A => 1.0,
+            Self::B => 0.0,
+            Self::Tie => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Match {
+    pub id: String,
+    pub problem_id: String,
+    pub contestant_a: String,
+    pub contestant_b: String,
+    pub winner: Outcome,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    pub contestant: String,
+    pub elo: f64,
+    /// Bradley-Terry strength on the Elo scale (1500 = even odds against the prior reference)
+    pub bradley_terry: f64,
+    pub matches: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub ties: i64,
+}
+
+impl From<EvalRatingRow> for Rating {
+    fn from(row: EvalRatingRow) -> Self {
+        Self {
+            contestant: row.contestant,
+            elo: row.elo,
+            bradley_terry: row.bradley_terry,
+            matches: row.matches,
+            wins: row.wins,
+            losses: row.losses,
+            ties: row.ties,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tournament {
+    pub id: String,
+    pub judge: String,
+    pub matches: Vec<Match>,
+    pub failures: Vec<EvalFailure>,
+    /// Ratings over every stored match, not only this tournament's
+    pub leaderboard: Vec<Rating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JudgeVerdict {
+    winner: String,
+    #[serde(default)]
+    reason: String,
+}
+
+/// Generate a bundle per contestant for every problem, have `judge` (`provider` or `provider:model`)
+/// decide every pair, store the matches and recompute the leaderboard.
+/// Contestants are keyed by recipe, provider and model, so an entrant without a target yields one per provider.
+pub fn run_tournament(
+    db_path: impl AsRef<Path>,
+    entrants: &[AbArm],
+    judge: &str,
+    problem_ids: &[String],
+    options: &TournamentOptions,
+) -> Result<Tournament, String> {
+    let storage = Storage::new(db_path).map_err(|e| e.to_string())?;
+    let (judge_provider, judge_model) = judge.split_once(':').unwrap_or((judge, ""));
+    let judge_adapter = target_adapter(judge_provider, (!judge_model.is_empty()).then_some(judge_model))
+        .map_err(|e| format!("Judge: {}", e))?;
+    let judge_label = format!("{}:{}", judge_adapter.provider_name(), judge_adapter.model_name());
+
+    let mut setups = Vec::new();
+    let mut problems: Vec<EvalProblem> = Vec::new();
+    for entrant in entrants {
+        let (recipe, entrant_problems) = load_recipe_and_problems(&storage, &entrant.recipe_id, problem_ids)?;
+        let adapters = entrant.adapters()?;
+        if adapters.is_empty() {
+            return Err("No providers available. Set OPENAI_API_KEY, ANTHROPIC_API_KEY or GEMINI_API_KEY.".to_string());
+        }
+        problems = entrant_problems;
+        setups.push((recipe, adapters));
+    }
+
+    let tournament_id = Uuid::new_v4().to_string();
+    let mut rng = SplitMix64::new(options.seed);
+    let mut matches = Vec::new();
+    let mut failures = Vec::new();
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        for problem in &problems {
+            // contestant label -> bundle, first bundle wins if two entrants collide
+            let mut contestants: BTreeMap<String, IdeaBundle> = BTreeMap::new();
+            for (recipe, adapters) in &setups {
+                let rubric = parse_rubric(&recipe.rubric_json);
+                let request = CompletionRequest::new(
+                    &build_system_prompt(recipe),
+                    &render_user_prompt(&recipe.user_prompt_template, problem),
+                )
+                .with_params(GenerationParams::from_json(recipe.params_json.as_deref())?);
+                let result = Orchestrator::new(adapters.clone()).run(&request, rubric.as_ref()).await;
+                storage
+                    .record_usage(
+                        &result.attempts,
+                        &UsageScope {
+                            recipe_id: Some(recipe.id.clone()),
+                            run_id: Some(tournament_id.clone()),
+                            ..Default::default()
+                        },
+                    )
+                    .map_err(|e| e.to_string())?;
+                failures.extend(result.errors.iter().map(|(provider, error)| EvalFailure {
+                    problem_id: problem.id.clone(),
+                    provider: provider.clone(),
+                    error: error.clone(),
+                }));
+                for bundle in result.all_bundles() {
+                    let label = format!("{} @ {}:{}", recipe.id, bundle.provider, bundle.model);
+                    contestants.entry(label).or_insert_with(|| bundle.clone());
+                }
+            }
+
+            let entries: Vec<(&String, &IdeaBundle)> = contestants.iter().collect();
+            for i in 0..entries.len() {
+                for j in (i + 1)..entries.len() {
+                    let (label_a, bundle_a) = entries[i];
+                    let (label_b, bundle_b) = entries[j];
+                    let swapped = rng.next_u64() & 1 == 1;
+                    let (judged, attempts) =
+                        usage::track(judge_pair(&*judge_adapter, problem, bundle_a, bundle_b, swapped)).await;
+                    // Judge calls belong to the tournament, not to either recipe
+                    let scope = UsageScope {
+                        run_id: Some(tournament_id.clone()),
+                        ..Default::default()
+                    };
+                    storage.record_usage(&attempts, &scope).map_err(|e| e.to_string())?;
+                    match judged {
+                        Ok((winner, reason)) => {
+                            let row = EvalMatchRow {
+                                id: Uuid::new_v4().to_string(),
+                                tournament_id: tournament_id.clone(),
+                                problem_id: problem.id.clone(),
+                                contestant_a: label_a.clone(),
+                                contestant_b: label_b.clone(),
+                                bundle_a_json: serde_json::to_string(bundle_a).map_err(|e| e.to_string())?,
+                                bundle_b_json: serde_json::to_string(bundle_b).map_err(|e| e.to_string())?,
+                                winner: winner.as_str().to_string(),
+                                reason: reason.clone(),
+                                judge: judge_label.clone(),
+                                created_at: chrono_utc_now(),
+                            };
+                            storage.insert_eval_match(&row).map_err(|e| e.to_string())?;
+                            matches.push(Match {
+                                id: row.id,
+                                problem_id: row.problem_id,
+                                contestant_a: row.contestant_a,
+                                contestant_b: row.contestant_b,
+                                winner,
+                                reason,
+                            });
+                        }
+                        Err(error) => failures.push(EvalFailure {
+                            problem_id: problem.id.clone(),
+                            provider: judge_label.clone(),
+                            error,
+                        }),
+                    }
+                }
+            }
+        }
+        Ok::<_, String>(())
+    })?;
+
+    let leaderboard = recompute_ratings(&storage, options.k_factor)?;
+    Ok(Tournament {
+        id: tournament_id,
+        judge: judge_label,
+        matches,
+        failures,
+        leaderboard,
+    })
+}
+
+/// Rebuild the stored leaderboard from every match on record
+pub fn recompute_ratings(storage: &Storage, k_factor: f64) -> Result<Vec<Rating>, String> {
+    let rows = storage.list_eval_matches(None).map_err(|e| e.to_string())?;
+    let games: Vec<(&str, &str, Outcome)> = rows
+        .iter()
+        .filter_map(|m| Some((m.contestant_a.as_str(), m.contestant_b.as_str(), Outcome::parse(&m.winner)?)))
+        .collect();
+    let mut ratings = compute_ratings(&games, k_factor);
+    ratings.sort_by(|a, b| b.elo.partial_cmp(&a.elo).unwrap_or(std::cmp::Ordering::Equal));
+
+    let now = chrono_utc_now();
+    let stored: Vec<EvalRatingRow> = ratings
+        .iter()
+        .map(|r| EvalRatingRow {
+            contestant: r.contestant.clone(),
+            elo: r.elo,
+            bradley_terry: r.bradley_terry,
+            matches: r.matches,
+            wins: r.wins,
+            losses: r.losses,
+            ties: r.ties,
+            updated_at: now.clone(),
+        })
+        .collect();
+    storage.replace_eval_ratings(&stored).map_err(|e| e.to_string())?;
+    Ok(ratings)
+}
+
+pub fn leaderboard(storage: &Storage) -> Result<Vec<Rating>, String> {
+    Ok(storage
+        .list_eval_ratings()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(Rating::from)
+        .collect())
+}
+
+/// Sequential Elo plus a Bradley-Terry fit over the same games
+pub fn compute_ratings(games: &[(&str, &str, Outcome)], k_factor: f64) -> Vec<Rating> {
+    let mut ratings: BTreeMap<&str, Rating> = BTreeMap::new();
+    for (a, b, _) in games {
+        for name in [*a, *b] {
+            ratings.entry(name).or_insert_with(|| Rating {
+                contestant: name.to_string(),
+                elo: INITIAL_RATING,
+                bradley_terry: INITIAL_RATING,
+                matches: 0,
+                wins: 0,
+                losses: 0,
+                ties: 0,
+            });
+        }
+    }
+
+    for (a, b, outcome) in games {
+        let (ra, rb) = (ratings[a].elo, ratings[b].elo);
+        let expected_a = 1.0 / (1.0 + 10f64.powf((rb - ra) / 400.0));
+        let delta = k_factor * (outcome.score_a() - expected_a);
+        for (name, sign, won, lost) in [(*a, 1.0, Outcome::A, Outcome::B), (*b, -1.0, Outcome::B, Outcome::A)] {
+            let r = ratings.get_mut(name).expect("rating seeded above");
+            r.elo += sign * delta;
+            r.matches += 1;
+            if *outcome == won {
+                r.wins += 1;
+            } else if *outcome == lost {
+                r.losses += 1;
+            } else {
+                r.ties += 1;
+            }
+        }
+    }
+
+    let names: Vec<&str> = ratings.keys().copied().collect();
+    let strengths = bradley_terry(&names, games);
+    for (name, strength) in names.iter().zip(strengths) {
+        ratings.get_mut(name).expect("rating seeded above").bradley_terry = INITIAL_RATING + 400.0 * strength.log10();
+    }
+    ratings.into_values().collect()
+}
+
+/// Bradley-Terry strengths by minorization-maximization. Ties count half a win each way, and every
+/// contestant gets one virtual tie against a reference of strength 1 so unbeaten records stay finite.
+fn bradley_terry(names: &[&str], games: &[(&str, &str, Outcome)]) -> Vec<f64> {
+    let index: BTreeMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let n = names.len();
+    let mut wins = vec![0.5; n];
+    let mut played = vec![vec![0.0; n]; n];
+    for (a, b, outcome) in games {
+        let (i, j) = (index[a], index[b]);
+        wins[i] += outcome.score_a();
+        wins[j] += 1.0 - outcome.score_a();
+        played[i][j] += 1.0;
+        played[j][i] += 1.0;
+    }
+
+    let mut p = vec![1.0; n];
+    for _ in 0..BT_ITERATIONS {
+        let next: Vec<f64> = (0..n)
+            .map(|i| {
+                let denom: f64 = (0..n)
+                    .filter(|&j| played[i][j] > 0.0)
+                    .map(|j| played[i][j] / (p[i] + p[j]))
+                    .sum::<f64>()
+                    + 1.0 / (p[i] + 1.0);
+                wins[i] / denom
+            })
+            .collect();
+        let converged = next.iter().zip(&p).all(|(x, y)| (x - y).abs() < 1e-9);
+        p = next;
+        if converged {
+            break;
+        }
+    }
+    p
+}
+
+/// Ask the judge about one pair. With `swapped`, B is shown first and the answer is mapped back.
+/// A judge that does not answer within the orchestrator's timeout fails the pair.
+async fn judge_pair(
+    judge: &dyn ProviderAdapter,
+    problem: &EvalProblem,
+    bundle_a: &IdeaBundle,
+    bundle_b: &IdeaBundle,
+    swapped: bool,
+) -> Result<(Outcome, String), String> {
+    let (first, second) = if swapped { (bundle_b, bundle_a) } else { (bundle_a, bundle_b) };
+    let user_prompt = format!(
+        "Problem:\n{}\n\nBundle A:\n{}\n\nBundle B:\n{}",
+        problem.content,
+        blind(first)?,
+        blind(second)?
+    );
+    let request = CompletionRequest::new(JUDGE_SYSTEM_PROMPT, &user_prompt);
+    let text = timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS), judge.complete_text(&request))
+        .await
+        .map_err(|_| format!("Judge timed out after {}s", DEFAULT_TIMEOUT_SECS))?
+        .map_err(|e| e.to_string())?;
+    let verdict = parse_verdict(&text)?;
+    let shown = Outcome::parse(&verdict.winner).ok_or_else(|| format!("Judge picked unknown winner: {}", verdict.winner))?;
+    let outcome = match (shown, swapped) {
+        (Outcome::A, true) => Outcome::B,
+        (Outcome::B, true) => Outcome::A,
+        (o, _) => o,
+    };
+    Ok((outcome, verdict.reason))
+}
+
+/// Bundle content without provider, model or IDs, so the judge cannot favour a name
+fn blind(bundle: &IdeaBundle) -> Result<String, String> {
+    let mut value = serde_json::to_value(bundle).map_err(|e| e.to_string())?;
+    if let Some(obj) = value.as_object_mut() {
+        for key in ["id", "provider", "model", "raw_response", "warnings", "repair_attempts", "params", "persona", "usage", "created_at"] {
+            obj.remove(key);
+        }
+    }
+    serde_json::to_string_pretty(&value).map_err(|e| e.to_string())
+}
+
+fn parse_verdict(text: &str) -> Result<JudgeVerdict, String> {
+    parse_lenient(text)
+        .map(|(verdict, _)| verdict)
+        .map_err(|e| format!("Judge verdict: {}", e))
+}
@@ -0,0 +1,353 @@
+//! Tolerant JSON extraction - pull the first JSON object out of model output and repair common defects
+
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+/// How far back truncation repair may cut before giving up
+const MAX_TRUNCATION_CUTS: usize = 64;
+
+/// One fix applied to the model output before it parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Took the contents of a ``` code fence
+    StrippedFence,
+    /// Dropped this many characters of prose before the object
+    StrippedLeadingText(usize),
+    /// Dropped this many characters after the object closed
+    StrippedTrailingText(usize),
+    RemovedTrailingCommas(usize),
+    /// Output ended mid-object: dropped the incomplete tail (or closed a string cut mid-value) and
+    /// added the missing closers. The bundle is missing whatever came after.
+    ClosedTruncated {
+        dropped: usize,
+        closed: usize,
+        cut_string: bool,
+    },
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StrippedFence => write!(f, "stripped code fence"),
+            Self::StrippedLeadingText(n) => write!(f, "stripped {} chars before the JSON", n),
+            Self::StrippedTrailingText(n) => write!(f, "stripped {} chars after the JSON", n),
+            Self::RemovedTrailingCommas(n) => write!(f, "removed {} trailing comma(s)", n),
+            Self::ClosedTruncated {
+                dropped,
+                closed,
+                cut_string,
+            } => write!(
+                f,
+                "output was truncated and may be incomplete: repaired by dropping {} chars, {}closing {} bracket(s)",
+                dropped,
+                if *cut_string { "ending a cut-off string, " } else { "" },
+                closed
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Extracted {
+    pub json: String,
+    pub repairs: Vec<Repair>,
+}
+
+/// Extract the first JSON object from `text`, repairing what it can. The result is guaranteed to parse.
+pub fn extract_json(text: &str) -> Result<Extracted, String> {
+    let mut repairs = Vec::new();
+    let mut body = text.trim();
+
+    if let Some(inner) = fenced_block(body) {
+        repairs.push(Repair::StrippedFence);
+        body = inner.trim();
+    }
+
+    let start = body
+        .find('{')
+        .ok_or_else(|| format!("No JSON object in model output: {}", preview(text)))?;
+    if start > 0 {
+        repairs.push(Repair::StrippedLeadingText(body[..start].chars().count()));
+    }
+    let body = &body[start..];
+
+    let scan = scan(body);
+    let mut json = match scan.end {
+        Some(end) => {
+            let trailing = body[end..].trim();
+            if !trailing.is_empty() {
+                repairs.push(Repair::StrippedTrailingText(trailing.chars().count()));
+            }
+            body[..end].to_string()
+        }
+        None => body.to_string(),
+    };
+
+    let (cleaned, commas) = remove_trailing_commas(&json);
+    if commas > 0 {
+        repairs.push(Repair::RemovedTrailingCommas(commas));
+        json = cleaned;
+    }
+
+    if scan.end.is_some() {
+        serde_json::from_str::<serde_json::Value>(&json).map_err(|e| format!("{}: {}", e, preview(&json)))?;
+        return Ok(Extracted { json, repairs });
+    }
+
+    let (repaired, repair) = close_truncated(&json)
+        .ok_or_else(|| format!("Truncated JSON could not be repaired: {}", preview(&json)))?;
+    repairs.push(repair);
+    Ok(Extracted { json: repaired, repairs })
+}
+
+/// Extract, repair and deserialize in one go
+pub fn parse_lenient<T: DeserializeOwned>(text: &str) -> Result<(T, Vec<Repair>), String> {
+    let extracted = extract_json(text)?;
+    let value = serde_json::from_str(&extracted.json).map_err(|e| format!("{}: {}", e, preview(&extracted.json)))?;
+    Ok((value, extracted.repairs))
+}
+
+/// Contents of the first ``` fence, if the text has one. An unterminated fence runs to the end.
+fn fenced_block(text: &str) -> Option<&str> {
+    let open = text.find("```")?;
+    // A fence after the object starts is part of a string value, not a wrapper
+    if text.find('{').is_some_and(|brace| brace < open) {
+        return None;
+    }
+    let after = &text[open + 3..];
+    // Skip the info string (`json`, `JSON`, ...) up to the end of the line
+    let content_start = after.find('\n').map(|i| i + 1).unwrap_or(after.len());
+    let info = after[..content_start].trim();
+    if !info.is_empty() && !info.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let content = &after[content_start..];
+    Some(match content.find("```") {
+        Some(close) => &content[..close],
+        None => content,
+    })
+}
+
+struct Scan {
+    /// Byte offset just past the closing brace of the first object
+    end: Option<usize>,
+    /// Open brackets at the end of input, innermost last
+    stack: Vec<char>,
+    in_string: bool,
+}
+
+fn scan(text: &str) -> Scan {
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => stack.push(c),
+            '}' | ']' => {
+                stack.pop();
+                if stack.is_empty() {
+                    return Scan {
+                        end: Some(i + 1),
+                        stack,
+                        in_string,
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+    Scan {
+        end: None,
+        stack,
+        in_string,
+    }
+}
+
+/// Drop commas that directly precede `}` or `]`, outside strings
+fn remove_trailing_commas(text: &str) -> (String, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut removed = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            out.push(c);
+            continue;
+        }
+        if c == '"' {
+            in_string = true;
+        }
+        if c == ',' {
+            let next = chars[i + 1..].iter().find(|n| !n.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                removed += 1;
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    (out, removed)
+}
+
+/// Close a truncated object. First tries closing an open string and the brackets as-is, then cuts
+/// back to earlier commas or openers so a half-written key or value is dropped.
+/// Returns the repaired JSON with the `Repair` to report, so a repaired truncation always ends up
+/// in the bundle's warnings.
+fn close_truncated(text: &str) -> Option<(String, Repair)> {
+    let mut cut = text.len();
+    for _ in 0..MAX_TRUNCATION_CUTS {
+        let prefix = text[..cut].trim_end().trim_end_matches(',');
+        let state = scan(prefix);
+        let mut candidate = prefix.to_string();
+        if state.in_string {
+            candidate.push('"');
+        }
+        for open in state.stack.iter().rev() {
+            candidate.push(if *open == '{' { '}' } else { ']' });
+        }
+        if serde_json::from_str::<serde_json::Value>(&candidate).is_ok() {
+            let repair = Repair::ClosedTruncated {
+                dropped: text[prefix.len()..].chars().count(),
+                closed: state.stack.len(),
+                cut_string: state.in_string,
+            };
+            return Some((candidate, repair));
+        }
+        cut = last_cut_point(text, prefix.len())?;
+    }
+    None
+}
+
+/// Byte offset of the last `,`, `{` or `[` outside a string before `limit`, keeping the opener itself
+fn last_cut_point(text: &str, limit: usize) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut last = None;
+    for (i, c) in text[..limit].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            ',' => last = Some(i),
+            '{' | '[' if i + 1 < limit => last = Some(i + 1),
+            _ => {}
+        }
+    }
+    last
+}
+
+fn preview(text: &str) -> String {
+    const MAX: usize = 200;
+    if text.chars().count() <= MAX {
+        text.to_string()
+    } else {
+        format!("{}...", text.chars().take(MAX).collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn lenient(text: &str) -> (Value, Vec<Repair>) {
+        parse_lenient::<Value>(text).unwrap()
+    }
+
+    #[test]
+    fn clean_object_needs_no_repair() {
+        assert_eq!(lenient(r#"{"a": [1, 2]}"#), (json!({"a": [1, 2]}), vec![]));
+    }
+
+    #[test]
+    fn strips_code_fence() {
+        let (value, repairs) = lenient("```json\n{\"a\": 1}\n```");
+        assert_eq!(value, json!({"a": 1}));
+        assert_eq!(repairs, vec![Repair::StrippedFence]);
+    }
+
+    #[test]
+    fn strips_prose_around_the_object() {
+        let (value, repairs) = lenient(r#"Here you go: {"a": 1} Hope it helps"#);
+        assert_eq!(value, json!({"a": 1}));
+        assert_eq!(repairs, vec![Repair::StrippedLeadingText(13), Repair::StrippedTrailingText(13)]);
+    }
+
+    #[test]
+    fn removes_trailing_commas_outside_strings() {
+        let (value, repairs) = lenient(r#"{"a": [1, 2,], "b": "x,}",}"#);
+        assert_eq!(value, json!({"a": [1, 2], "b": "x,}"}));
+        assert_eq!(repairs, vec![Repair::RemovedTrailingCommas(2)]);
+    }
+
+    #[test]
+    fn closes_nested_truncation() {
+        let (value, repairs) = lenient(r#"{"a": {"b": [1, 2"#);
+        assert_eq!(value, json!({"a": {"b": [1, 2]}}));
+        assert_eq!(
+            repairs,
+            vec![Repair::ClosedTruncated {
+                dropped: 0,
+                closed: 3,
+                cut_string: false
+            }]
+        );
+    }
+
+    #[test]
+    fn ends_a_cut_off_string() {
+        let (value, repairs) = lenient(r#"{"ideas": [{"title": "Weekly newsl"#);
+        assert_eq!(value, json!({"ideas": [{"title": "Weekly newsl"}]}));
+        assert_eq!(
+            repairs,
+            vec![Repair::ClosedTruncated {
+                dropped: 0,
+                closed: 3,
+                cut_string: true
+            }]
+        );
+    }
+
+    #[test]
+    fn drops_a_string_cut_inside_an_escape() {
+        let (value, repairs) = lenient(r#"{"a": 1, "b": "line\"#);
+        assert_eq!(value, json!({"a": 1}));
+        assert!(matches!(repairs[..], [Repair::ClosedTruncated { dropped: 13, closed: 1, .. }]), "{:?}", repairs);
+    }
+
+    #[test]
+    fn drops_a_half_written_key() {
+        let (value, _) = lenient(r#"{"a": 1, "b"#);
+        assert_eq!(value, json!({"a": 1}));
+    }
+
+    #[test]
+    fn does_not_repair_invalid_complete_json() {
+        assert!(extract_json(r#"{"a": tru}"#).is_err());
+        assert!(extract_json("no JSON here").is_err());
+    }
+}
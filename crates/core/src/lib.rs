@@ -1,14 +1,28 @@
 //! Idea Engine Core - Domain logic for AI orchestration, adapters, ranker
 
 pub mod adapters;
+pub mod catalog;
+pub mod clarify;
+pub mod codegen;
+pub mod context;
+pub mod decompose;
+pub mod drill;
 pub mod eval;
+pub mod json_repair;
+pub mod normalize;
 pub mod orchestrator;
+pub mod persona;
 pub mod ranker;
+pub mod request;
+pub mod response_schema;
 pub mod schema;
 pub mod storage;
+pub mod usage;
+pub mod validate;
 
 pub use adapters::{AnthropicAdapter, GeminiAdapter, OpenAIAdapter};
 pub use orchestrator::Orchestrator;
 pub use ranker::Ranker;
+pub use request::{CompletionRequest, GenerationParams};
 pub use schema::IdeaBundle;
 pub use storage::{RecipeRow, Storage};
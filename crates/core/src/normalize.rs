@@ -0,0 +1,326 @@
+//! Lenient AIResponse normalization - field aliases, missing arrays, coerced orders and enum values
+
+use crate::schema::{
+    normalize_level, normalize_priority, AIResponse, Complexity, EffortEstimate, Idea, NextAction, Priority, Risk,
+    Severity, Step,
+};
+use serde_json::{Map, Value};
+
+const STEP_PLAN_ALIASES: [&str; 4] = ["step_plan", "stepPlan", "steps", "plan"];
+const NEXT_ACTIONS_ALIASES: [&str; 4] = ["next_actions", "nextActions", "next_steps", "nextSteps"];
+
+/// Turn any reasonably shaped model JSON into an `AIResponse`. Every guess it makes is described in
+/// the returned warnings; a non-object top level or no usable idea is an error.
+pub fn normalize_response(value: Value) -> Result<(AIResponse, Vec<String>), String> {
+    let Value::Object(mut obj) = value else {
+        return Err("Model output is not a JSON object".to_string());
+    };
+    let mut warnings = Vec::new();
+
+    let ideas: Vec<Idea> = take_array(&mut obj, &["ideas"], &mut warnings)
+        .into_iter()
+        .filter_map(|v| idea(v, &mut warnings))
+        .collect();
+    if ideas.is_empty() {
+        return Err("Model output has no ideas".to_string());
+    }
+    let step_plan = take_array(&mut obj, &STEP_PLAN_ALIASES, &mut warnings)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, v)| step(i, v, &mut warnings))
+        .collect();
+    let risks = take_array(&mut obj, &["risks"], &mut warnings)
+        .into_iter()
+        .filter_map(|v| risk(v, &mut warnings))
+        .collect();
+    let dependencies = take_array(&mut obj, &["dependencies"], &mut warnings)
+        .into_iter()
+        .filter_map(|v| match v {
+            Value::String(s) => Some(s),
+            Value::Null => None,
+            Value::Object(ref o) => o.get("name").and_then(Value::as_str).map(String::from).or_else(|| Some(v.to_string())),
+            other => Some(other.to_string()),
+        })
+        .collect();
+    let effort = effort(obj.remove("effort"), &mut warnings);
+    let next_actions = take_array(&mut obj, &NEXT_ACTIONS_ALIASES, &mut warnings)
+        .into_iter()
+        .filter_map(|v| next_action(v, &mut warnings))
+        .collect();
+
+    Ok((
+        AIResponse {
+            ideas,
+            step_plan,
+            risks,
+            dependencies,
+            effort,
+            next_actions,
+        },
+        warnings,
+    ))
+}
+
+/// First present alias, as an array. A single object or string is wrapped; anything else is dropped.
+fn take_array(obj: &mut Map<String, Value>, aliases: &[&str], warnings: &mut Vec<String>) -> Vec<Value> {
+    let canonical = aliases[0];
+    let Some((key, value)) = aliases.iter().find_map(|k| obj.remove(*k).map(|v| (*k, v))) else {
+        warnings.push(format!("missing `{}`, defaulted to []", canonical));
+        return Vec::new();
+    };
+    if key != canonical {
+        warnings.push(format!("read `{}` as `{}`", key, canonical));
+    }
+    match value {
+        Value::Array(items) => items,
+        Value::Null => {
+            warnings.push(format!("`{}` was null, defaulted to []", canonical));
+            Vec::new()
+        }
+        single @ (Value::Object(_) | Value::String(_)) => {
+            warnings.push(format!("`{}` was a single value, wrapped in an array", canonical));
+            vec![single]
+        }
+        _ => {
+            warnings.push(format!("`{}` was not an array, defaulted to []", canonical));
+            Vec::new()
+        }
+    }
+}
+
+fn text(obj: &Map<String, Value>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|k| match obj.get(*k)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+fn idea(value: Value, warnings: &mut Vec<String>) -> Option<Idea> {
+    match value {
+        Value::String(s) => {
+            warnings.push("idea given as a string, used as title and description".to_string());
+            Some(Idea {
+                title: s.clone(),
+                description: s,
+                rationale: None,
+            })
+        }
+        Value::Object(o) => {
+            let title = text(&o, &["title", "name", "idea"]);
+            let description = text(&o, &["description", "summary", "details"]);
+            let (title, description) = match (title, description) {
+                (Some(t), Some(d)) => (t, d),
+                (Some(t), None) => {
+                    warnings.push(format!("idea \"{}\" has no description", t));
+                    (t.clone(), t)
+                }
+                (None, Some(d)) => {
+                    warnings.push("idea has no title, used its description".to_string());
+                    (d.clone(), d)
+                }
+                (None, None) => {
+                    warnings.push("dropped an idea with no title or description".to_string());
+                    return None;
+                }
+            };
+            Some(Idea {
+                title,
+                description,
+                rationale: text(&o, &["rationale", "reason", "why"]),
+            })
+        }
+        _ => {
+            warnings.push("dropped an idea that is not an object".to_string());
+            None
+        }
+    }
+}
+
+fn step(index: usize, value: Value, warnings: &mut Vec<String>) -> Option<Step> {
+    let fallback_order = index as i32 + 1;
+    match value {
+        Value::String(action) => {
+            warnings.push(format!("step {} given as a string", fallback_order));
+            Some(Step {
+                order: fallback_order,
+                action,
+                details: None,
+            })
+        }
+        Value::Object(o) => {
+            let Some(action) = text(&o, &["action", "title", "step", "description"]) else {
+                warnings.push(format!("dropped step {} with no action", fallback_order));
+                return None;
+            };
+            let order = match o.get("order").or_else(|| o.get("step_number")) {
+                Some(Value::Number(n)) => n.as_i64().map(|n| n as i32),
+                // "3" or "Step 3"; "1.2", "2-3" or "Step 3 of 10" are ambiguous and fall back to the position
+                Some(Value::String(s)) => {
+                    let runs: Vec<&str> = s.split(|c: char| !c.is_ascii_digit()).filter(|p| !p.is_empty()).collect();
+                    let parsed = match runs[..] {
+                        [digits] => digits.parse().ok(),
+                        _ => None,
+                    };
+                    match parsed {
+                        Some(order) => {
+                            warnings.push(format!("step order \"{}\" coerced to a number", s));
+                            Some(order)
+                        }
+                        None => {
+                            warnings.push(format!(
+                                "step order \"{}\" is not a single number, used its position {}",
+                                s, fallback_order
+                            ));
+                            Some(fallback_order)
+                        }
+                    }
+                }
+                _ => None,
+            };
+            let order = order.unwrap_or_else(|| {
+                warnings.push(format!("step \"{}\" has no order, used its position {}", action, fallback_order));
+                fallback_order
+            });
+            let details = text(&o, &["details", "description", "notes"]).filter(|d| *d != action);
+            Some(Step { order, action, details })
+        }
+        _ => {
+            warnings.push(format!("dropped step {} that is not an object", fallback_order));
+            None
+        }
+    }
+}
+
+fn risk(value: Value, warnings: &mut Vec<String>) -> Option<Risk> {
+    let (description, severity, mitigation) = match value {
+        Value::String(s) => {
+            warnings.push("risk given as a string".to_string());
+            (s, None, None)
+        }
+        Value::Object(o) => {
+            let Some(description) = text(&o, &["description", "risk", "title"]) else {
+                warnings.push("dropped a risk with no description".to_string());
+                return None;
+            };
+            (
+                description,
+                text(&o, &["severity", "level", "impact"]),
+                text(&o, &["mitigation", "mitigations"]),
+            )
+        }
+        _ => {
+            warnings.push("dropped a risk that is not an object".to_string());
+            return None;
+        }
+    };
+    let severity = match severity {
+        Some(raw) => match normalize_level(&raw) {
+            Some(level) => {
+                if level != raw {
+                    warnings.push(format!("severity \"{}\" mapped to \"{}\"", raw, level));
+                }
+                level.to_string()
+            }
+            None => {
+                warnings.push(format!("unknown severity \"{}\"", raw));
+                raw.trim().to_lowercase()
+            }
+        },
+        None => {
+            warnings.push(format!("risk \"{}\" has no severity, defaulted to medium", description));
+            "medium".to_string()
+        }
+    };
+    Some(Risk {
+        description,
+        severity: Severity::from(severity),
+        mitigation,
+    })
+}
+
+fn effort(value: Option<Value>, warnings: &mut Vec<String>) -> EffortEstimate {
+    match value {
+        Some(Value::Object(o)) => {
+            let complexity = text(&o, &["complexity", "difficulty"]).map(|raw| match normalize_level(&raw) {
+                Some(level) => {
+                    if level != raw {
+                        warnings.push(format!("complexity \"{}\" mapped to \"{}\"", raw, level));
+                    }
+                    level.to_string()
+                }
+                None => {
+                    warnings.push(format!("unknown complexity \"{}\"", raw));
+                    raw.trim().to_lowercase()
+                }
+            });
+            let time = text(&o, &["time", "duration", "timeline", "estimate"]).unwrap_or_else(|| {
+                warnings.push("effort has no time".to_string());
+                String::new()
+            });
+            EffortEstimate {
+                time,
+                cost: text(&o, &["cost", "budget"]),
+                complexity: complexity.map(Complexity::from),
+            }
+        }
+        Some(Value::String(time)) => {
+            warnings.push("effort given as a string, used as time".to_string());
+            EffortEstimate {
+                time,
+                cost: None,
+                complexity: None,
+            }
+        }
+        _ => {
+            warnings.push("missing `effort`".to_string());
+            EffortEstimate {
+                time: String::new(),
+                cost: None,
+                complexity: None,
+            }
+        }
+    }
+}
+
+fn next_action(value: Value, warnings: &mut Vec<String>) -> Option<NextAction> {
+    let (action, priority) = match value {
+        Value::String(action) => {
+            warnings.push("next action given as a string".to_string());
+            (action, None)
+        }
+        Value::Object(o) => {
+            let Some(action) = text(&o, &["action", "title", "description"]) else {
+                warnings.push("dropped a next action with no action".to_string());
+                return None;
+            };
+            (action, text(&o, &["priority", "timeframe", "when"]))
+        }
+        _ => {
+            warnings.push("dropped a next action that is not an object".to_string());
+            return None;
+        }
+    };
+    let priority = match priority {
+        Some(raw) => match normalize_priority(&raw) {
+            Some(p) => {
+                if p != raw {
+                    warnings.push(format!("priority \"{}\" mapped to \"{}\"", raw, p));
+                }
+                p.to_string()
+            }
+            None => {
+                warnings.push(format!("unknown priority \"{}\"", raw));
+                raw.trim().to_lowercase()
+            }
+        },
+        None => {
+            warnings.push(format!("next action \"{}\" has no priority, defaulted to medium", action));
+            "medium".to_string()
+        }
+    };
+    Some(NextAction {
+        action,
+        priority: Priority::from(priority),
+    })
+}
@@ -1,27 +1,47 @@
-//! Orchestrator - fan out to providers, timeout, retry
+//! Orchestrator - fan out to providers, timeout, retry, repair invalid bundles
 
-use crate::adapters::{AdapterError, ProviderAdapter};
-use crate::ranker::Ranker;
+use crate::adapters::{target_label, AdapterError, ProviderAdapter};
+use crate::decompose::{assemble, plan, sub_problem_prompt, HierarchicalPlan, PlanSection, SubProblem};
+use crate::persona::Persona;
+use crate::ranker::{Ranked, Ranker};
+use crate::request::{CompletionRequest, GenerationParams, Message};
 use crate::schema::IdeaBundle;
+use crate::usage::{self, Attempt, AttemptSink, Usage};
+use crate::validate::{validate, Violation};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::timeout;
 
-const DEFAULT_TIMEOUT_SECS: u64 = 60;
+/// Seconds one provider call may take before it is abandoned
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
 const DEFAULT_RETRIES: u32 = 2;
+const DEFAULT_REPAIR_ATTEMPTS: u32 = 0;
+/// First retry waits this long, doubling after each further failure
+const RETRY_BACKOFF_MS: u64 = 500;
+/// A longer Retry-After fails the call instead of stalling the run
+const MAX_RETRY_AFTER_SECS: u64 = 60;
 
 pub struct Orchestrator {
     adapters: Vec<Arc<dyn ProviderAdapter>>,
+    /// Per-adapter overrides of the request's params, in adapter order
+    adapter_params: Vec<Option<GenerationParams>>,
     timeout_secs: u64,
     retries: u32,
+    repair_attempts: u32,
+    personas: Vec<Persona>,
 }
 
 impl Orchestrator {
     pub fn new(adapters: Vec<Arc<dyn ProviderAdapter>>) -> Self {
         Self {
+            adapter_params: vec![None; adapters.len()],
             adapters,
             timeout_secs: DEFAULT_TIMEOUT_SECS,
             retries: DEFAULT_RETRIES,
+            repair_attempts: DEFAULT_REPAIR_ATTEMPTS,
+            personas: Vec::new(),
         }
     }
 
@@ -35,67 +55,185 @@ impl Orchestrator {
         self
     }
 
-    /// Fan out the same prompt to all adapters, collect results, rank and merge
-    pub async fn run(
-        &self,
-        system_prompt: &str,
-        user_prompt: &str,
-        rubric: Option<&[f64; 6]>,
-    ) -> OrchestratorResult {
-        let mut tasks = Vec::with_capacity(self.adapters.len());
-        for adapter in &self.adapters {
-            let sys = system_prompt.to_string();
-            let usr = user_prompt.to_string();
-            let ad = Arc::clone(adapter);
-            let timeout_secs = self.timeout_secs;
-            let retries = self.retries;
-            tasks.push(tokio::spawn(async move {
-                run_with_retry(&*ad, &sys, &usr, timeout_secs, retries).await
-            }));
+    /// Re-ask a provider whose bundle fails validation, listing the violations, up to `attempts` times
+    pub fn with_repair_attempts(mut self, attempts: u32) -> Self {
+        self.repair_attempts = attempts;
+        self
+    }
+
+    /// Generation params per adapter, in the order the adapters were given. Set fields override the
+    /// request's params for that adapter only.
+    pub fn with_adapter_params(mut self, params: Vec<Option<GenerationParams>>) -> Self {
+        self.adapter_params = params;
+        self.adapter_params.resize(self.adapters.len(), None);
+        self
+    }
+
+    /// The first adapter, which plans decomposed runs
+    pub fn planner(&self) -> Option<&dyn ProviderAdapter> {
+        self.adapters.first().map(|a| &**a)
+    }
+
+    /// Run every provider once per persona, each with the persona's overlay on the system prompt.
+    /// Bundles are tagged with the persona and ranking keeps at least one bundle per persona.
+    pub fn with_personas(mut self, personas: Vec<Persona>) -> Self {
+        self.personas = personas;
+        self
+    }
+
+    /// Fan out the same request to all adapters (and personas), collect results, rank and merge
+    pub async fn run(&self, request: &CompletionRequest, rubric: Option<&[f64; 6]>) -> OrchestratorResult {
+        let personas: Vec<Option<&Persona>> = if self.personas.is_empty() {
+            vec![None]
+        } else {
+            self.personas.iter().map(Some).collect()
+        };
+        let mut tasks = Vec::with_capacity(self.adapters.len() * personas.len());
+        for (adapter, params) in self.adapters.iter().zip(&self.adapter_params) {
+            for persona in &personas {
+                let mut request = request.clone();
+                if let Some(params) = params {
+                    request.params = params.merged_over(&request.params);
+                }
+                if let Some(persona) = persona {
+                    request.system_prompt = persona.apply(&request.system_prompt);
+                }
+                let persona_id = persona.map(|p| p.id.clone());
+                let label = target_label(&**adapter);
+                let ad = Arc::clone(adapter);
+                let timeout_secs = self.timeout_secs;
+                let retries = self.retries;
+                let repair_attempts = self.repair_attempts;
+                // Outside the task, so calls made before a panic or cancellation are still counted
+                let sink = AttemptSink::default();
+                let task = tokio::spawn(usage::track_into(Arc::clone(&sink), async move {
+                    let bundle = run_with_retry(&*ad, &request, timeout_secs, retries).await?;
+                    let mut bundle = repair_invalid(&*ad, &request, bundle, timeout_secs, repair_attempts).await;
+                    bundle.persona = persona_id;
+                    Ok::<_, AdapterError>(bundle)
+                }));
+                tasks.push((label, persona_id_label(persona), task, sink));
+            }
         }
 
         let mut bundles = Vec::new();
         let mut errors = Vec::new();
+        let mut attempts = Vec::new();
 
-        for (i, task) in tasks.into_iter().enumerate() {
-            match task.await {
-                Ok(Ok(bundle)) => bundles.push(bundle),
-                Ok(Err(e)) => errors.push((self.adapters[i].provider_name().to_string(), e.to_string())),
-                Err(e) => errors.push((self.adapters[i].provider_name().to_string(), e.to_string())),
+        for (label, persona, task, sink) in tasks {
+            let result = task.await;
+            let task_attempts = std::mem::take(&mut *sink.lock().unwrap());
+            match result {
+                Ok(Ok(mut bundle)) => {
+                    bundle.usage = Some(Usage::from_attempts(&task_attempts));
+                    bundles.push(bundle);
+                }
+                Ok(Err(e)) => errors.push((label, format!("{}{}", persona, e))),
+                Err(e) => errors.push((label, format!("{}{}", persona, e))),
             }
+            attempts.extend(task_attempts);
         }
 
         let ranked = if bundles.is_empty() {
-            Vec::new()
+            Ranked::default()
+        } else if self.personas.is_empty() {
+            Ranker::new(rubric).rank_and_merge(bundles)
         } else {
-            let ranker = Ranker::new(rubric);
-            ranker.rank_and_merge(bundles)
+            Ranker::new(rubric).rank_covering_personas(bundles)
         };
 
         OrchestratorResult {
-            bundles: ranked,
+            bundles: ranked.bundles,
+            merged: ranked.merged,
             errors,
+            attempts,
         }
     }
+
+    /// Decomposition mode: `planner` splits the problem into sub-problems, each one is fanned out and
+    /// ranked like a normal run - in dependency order, seeing the plans it builds on - and the top
+    /// bundles are assembled into one plan. If the planner fails the problem is solved whole.
+    pub async fn run_decomposed(
+        &self,
+        planner: &dyn ProviderAdapter,
+        request: &CompletionRequest,
+        rubric: Option<&[f64; 6]>,
+    ) -> HierarchicalPlan {
+        let problem = request.user_prompt().to_string();
+        let (planned, mut attempts) =
+            usage::track(timeout(Duration::from_secs(self.timeout_secs), plan(planner, request))).await;
+        let (subs, mut warnings) = match planned {
+            Ok(Ok((subs, warnings))) if subs.len() > 1 => (subs, warnings),
+            Ok(Ok(_)) => (Vec::new(), vec!["planner returned fewer than two sub-problems".to_string()]),
+            Ok(Err(e)) => (Vec::new(), vec![e]),
+            Err(_) => (Vec::new(), vec!["Planner timed out".to_string()]),
+        };
+
+        if subs.is_empty() {
+            warnings.push("solved as a single problem".to_string());
+            let mut result = self.run(request, rubric).await;
+            attempts.append(&mut result.attempts);
+            let whole = SubProblem {
+                id: "whole".to_string(),
+                title: "Whole problem".to_string(),
+                description: problem,
+                depends_on: Vec::new(),
+            };
+            return assemble(vec![section(whole, result)], warnings, attempts);
+        }
+
+        let mut sections: Vec<PlanSection> = Vec::with_capacity(subs.len());
+        for sub in subs {
+            let mut sub_request = request.clone();
+            sub_request.messages = request.history().to_vec();
+            sub_request.messages.push(Message::user(sub_problem_prompt(&problem, &sub, &sections)));
+            let mut result = self.run(&sub_request, rubric).await;
+            attempts.append(&mut result.attempts);
+            sections.push(section(sub, result));
+        }
+        assemble(sections, warnings, attempts)
+    }
+}
+
+/// "persona_id: " to prefix an error with, or nothing
+fn persona_id_label(persona: &Option<&Persona>) -> String {
+    persona.map(|p| format!("{}: ", p.id)).unwrap_or_default()
+}
+
+fn section(sub_problem: SubProblem, result: OrchestratorResult) -> PlanSection {
+    PlanSection {
+        sub_problem,
+        bundle: result.bundles.into_iter().next(),
+        errors: result.errors,
+    }
 }
 
 async fn run_with_retry(
     adapter: &dyn ProviderAdapter,
-    system_prompt: &str,
-    user_prompt: &str,
+    request: &CompletionRequest,
     timeout_secs: u64,
     retries: u32,
 ) -> Result<IdeaBundle, AdapterError> {
     let mut last_err = None;
-    for _ in 0..=retries {
-        let result = timeout(
-            Duration::from_secs(timeout_secs),
-            adapter.complete(system_prompt, user_prompt),
-        )
-        .await;
+    for attempt in 0..=retries {
+        if let Some(e) = &last_err {
+            tokio::time::sleep(retry_delay(e, attempt - 1)).await;
+        }
+        let result = timeout(Duration::from_secs(timeout_secs), adapter.complete(request)).await;
 
         match result {
             Ok(Ok(bundle)) => return Ok(bundle),
+            // A refusal or rejected parameter repeats on retry, and truncation was already retried
+            // at the highest limit
+            Ok(Err(e @ (AdapterError::Blocked(_) | AdapterError::Truncated(_) | AdapterError::Unsupported(_)))) => {
+                return Err(e)
+            }
+            Ok(Err(AdapterError::RateLimited { message, retry_after: Some(secs) })) if secs > MAX_RETRY_AFTER_SECS => {
+                return Err(AdapterError::RateLimited {
+                    message,
+                    retry_after: Some(secs),
+                })
+            }
             Ok(Err(e)) => last_err = Some(e),
             Err(_) => last_err = Some(AdapterError::Api("Timeout".into())),
         }
@@ -103,7 +241,149 @@ async fn run_with_retry(
     Err(last_err.unwrap_or(AdapterError::Api("Unknown".into())))
 }
 
+/// How long to wait before retrying after the `n`th failure (from 0): the provider's Retry-After when
+/// it sent one, else exponential backoff
+fn retry_delay(error: &AdapterError, n: u32) -> Duration {
+    match error {
+        AdapterError::RateLimited {
+            retry_after: Some(secs),
+            ..
+        } => Duration::from_secs(*secs),
+        _ => Duration::from_millis(RETRY_BACKOFF_MS << n.min(6)),
+    }
+}
+
+/// Validate the bundle and, while violations remain and attempts are left, continue the conversation
+/// with the provider's previous answer and a turn listing the violations, asking for a corrected one.
+/// A replacement is kept only if it has fewer violations; whatever is still wrong ends up in the
+/// bundle's warnings.
+async fn repair_invalid(
+    adapter: &dyn ProviderAdapter,
+    request: &CompletionRequest,
+    mut bundle: IdeaBundle,
+    timeout_secs: u64,
+    attempts: u32,
+) -> IdeaBundle {
+    let original = validate(&bundle);
+    let mut violations = original.clone();
+    let mut used = 0;
+    let mut improved = false;
+    while !violations.is_empty() && used < attempts {
+        used += 1;
+        let follow_up = repair_request(request, &bundle, &violations);
+        let result = timeout(Duration::from_secs(timeout_secs), adapter.complete(&follow_up)).await;
+        let Ok(Ok(candidate)) = result else {
+            continue;
+        };
+        let remaining = validate(&candidate);
+        if remaining.len() < violations.len() {
+            bundle = candidate;
+            violations = remaining;
+            improved = true;
+        }
+    }
+    // Every follow-up counts, including failed ones and those that fixed nothing
+    bundle.repair_attempts = used;
+    if improved {
+        let fixed: Vec<String> = original.iter().filter(|v| !violations.contains(v)).map(ToString::to_string).collect();
+        bundle.warnings.push(format!(
+            "repaired after {} follow-up request(s): {}",
+            used,
+            fixed.join("; ")
+        ));
+    } else if used > 0 {
+        bundle
+            .warnings
+            .push(format!("{} follow-up request(s) did not fix any violation", used));
+    }
+    bundle
+        .warnings
+        .extend(violations.iter().map(|v| format!("invalid: {}", v)));
+    bundle
+}
+
+fn repair_request(request: &CompletionRequest, bundle: &IdeaBundle, violations: &[Violation]) -> CompletionRequest {
+    let previous = bundle
+        .raw_response
+        .clone()
+        .unwrap_or_else(|| serde_json::to_string(bundle).unwrap_or_default());
+    let problems: Vec<String> = violations.iter().map(|v| format!("- {}", v)).collect();
+    let mut follow_up = request.clone();
+    follow_up.messages.push(Message::assistant(previous));
+    follow_up.messages.push(Message::user(format!(
+        "Your previous answer has these problems:\n{}\n\nReturn the complete corrected JSON object with every problem fixed.",
+        problems.join("\n")
+    )));
+    follow_up
+}
+
 pub struct OrchestratorResult {
     pub bundles: Vec<IdeaBundle>,
+    /// Near-duplicates dropped in ranking, with the id of the kept bundle each one duplicates
+    pub merged: Vec<(IdeaBundle, String)>,
+    /// (target "provider:model", error)
     pub errors: Vec<(String, String)>,
+    /// Every provider call of the run, including those behind failed and merged-away bundles
+    pub attempts: Vec<Attempt>,
+}
+
+/// What one target produced in a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetOutcome {
+    /// Ids of the target's bundles that survived ranking, best first
+    pub bundle_ids: Vec<String>,
+    /// Ids of the kept bundles this target's near-duplicates were merged into
+    pub merged_into: Vec<String>,
+    pub errors: Vec<String>,
+    pub usage: Usage,
+}
+
+impl OrchestratorResult {
+    /// Bundles, merges and errors grouped by "provider:model" target
+    pub fn by_target(&self) -> BTreeMap<String, TargetOutcome> {
+        let mut targets: BTreeMap<String, TargetOutcome> = BTreeMap::new();
+        for bundle in &self.bundles {
+            targets.entry(bundle.target()).or_default().bundle_ids.push(bundle.id.clone());
+        }
+        for (bundle, kept_id) in &self.merged {
+            let merged_into = &mut targets.entry(bundle.target()).or_default().merged_into;
+            if !merged_into.contains(kept_id) {
+                merged_into.push(kept_id.clone());
+            }
+        }
+        for (target, error) in &self.errors {
+            targets.entry(target.clone()).or_default().errors.push(error.clone());
+        }
+        for attempt in &self.attempts {
+            targets.entry(attempt.target()).or_default().usage.add(attempt);
+        }
+        targets
+    }
+
+    /// Every bundle the run produced, kept ones first, then those merged away as near-duplicates
+    pub fn all_bundles(&self) -> impl Iterator<Item = &IdeaBundle> {
+        self.bundles.iter().chain(self.merged.iter().map(|(bundle, _)| bundle))
+    }
+
+    /// Totals for the whole run
+    pub fn usage(&self) -> Usage {
+        Usage::from_attempts(&self.attempts)
+    }
+
+    /// Assistant message text stored next to the bundles: the bundle count and any provider errors
+    pub fn summary(&self) -> String {
+        let errors = self
+            .errors
+            .iter()
+            .map(|(p, e)| format!("{}: {}", p, e))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if self.bundles.is_empty() {
+            format!("No ideas generated. Errors: {}", errors)
+        } else {
+            format!("Generated {} idea bundle(s). {}", self.bundles.len(), errors)
+                .trim_end()
+                .to_string()
+        }
+    }
 }
@@ -0,0 +1,81 @@
+//! Personas - system-prompt overlays that make each provider answer from a different viewpoint
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Persona {
+    /// Stable key stored on each bundle, e.g. "frugal_founder"
+    pub id: String,
+    pub name: String,
+    /// Appended to the system prompt
+    pub overlay: String,
+}
+
+impl Persona {
+    pub fn new(id: &str, name: &str, overlay: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            overlay: overlay.to_string(),
+        }
+    }
+
+    /// The system prompt with this persona's overlay after it
+    pub fn apply(&self, system_prompt: &str) -> String {
+        format!("{}\n\nAnswer as a {}. {}", system_prompt, self.name.to_lowercase(), self.overlay)
+    }
+}
+
+/// The personas offered out of the box
+pub fn builtin_personas() -> Vec<Persona> {
+    vec![
+        Persona::new(
+            "pragmatic_engineer",
+            "Pragmatic engineer",
+            "Favour ideas that can be built with proven tools and a small team, name the technical work \
+             concretely and avoid anything that needs research to succeed.",
+        ),
+        Persona::new(
+            "frugal_founder",
+            "Frugal founder",
+            "Assume almost no money. Prefer free tiers, manual work before automation and ideas that pay \
+             for themselves quickly; put a cost on every step.",
+        ),
+        Persona::new(
+            "skeptical_risk_officer",
+            "Skeptical risk officer",
+            "Look for what can go wrong first. Prefer reversible, low-exposure ideas, list legal, security \
+             and operational risks with a mitigation for each.",
+        ),
+        Persona::new(
+            "growth_marketer",
+            "Growth marketer",
+            "Focus on reaching and converting the audience: channels, hooks, experiments and the metrics \
+             that show whether an idea is working.",
+        ),
+    ]
+}
+
+/// A built-in persona by id, or a full custom persona
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PersonaSpec {
+    Builtin(String),
+    Custom(Persona),
+}
+
+/// Resolve specs to personas; unknown built-in ids are an error
+pub fn resolve_personas(specs: &[PersonaSpec]) -> Result<Vec<Persona>, String> {
+    let builtin = builtin_personas();
+    specs
+        .iter()
+        .map(|spec| match spec {
+            PersonaSpec::Builtin(id) => builtin
+                .iter()
+                .find(|p| &p.id == id)
+                .cloned()
+                .ok_or_else(|| format!("Unknown persona: {}", id)),
+            PersonaSpec::Custom(persona) => Ok(persona.clone()),
+        })
+        .collect()
+}
@@ -1,22 +1,34 @@
 //! Ranker - apply rubric, dedupe, pick top ideas
 
-use crate::schema::{IdeaBundle, ScoreCard};
+use crate::schema::{IdeaBundle, ScoreCard, Severity};
 
 const DEFAULT_RUBRIC: [f64; 6] = [1.5, 2.0, 1.0, 1.0, 1.5, 2.0]; // novelty, feasibility, cost, time, risk, clarity
+const RUBRIC_KEYS: [&str; 6] = ["novelty", "feasibility", "cost", "time", "risk", "clarity"];
 
 pub struct Ranker {
     rubric: [f64; 6],
 }
 
+type Scored = (IdeaBundle, ScoreCard);
+
+/// Bundles after ranking, and the near-duplicates that were merged away
+#[derive(Debug, Default)]
+pub struct Ranked {
+    /// Best first
+    pub bundles: Vec<IdeaBundle>,
+    /// (dropped bundle, id of the kept bundle it duplicates)
+    pub merged: Vec<(IdeaBundle, String)>,
+}
+
 impl Ranker {
     pub fn new(rubric: Option<&[f64; 6]>) -> Self {
         Self {
-            rubric: rubric.map(|r| *r).unwrap_or(DEFAULT_RUBRIC),
+            rubric: rubric.copied().unwrap_or(DEFAULT_RUBRIC),
         }
     }
 
     /// Score each bundle, dedupe by similarity, return sorted by total score
-    pub fn rank_and_merge(&self, bundles: Vec<IdeaBundle>) -> Vec<IdeaBundle> {
+    pub fn rank_and_merge(&self, bundles: Vec<IdeaBundle>) -> Ranked {
         let scored: Vec<(IdeaBundle, ScoreCard)> = bundles
             .into_iter()
             .map(|b| {
@@ -25,12 +37,54 @@ impl Ranker {
             })
             .collect();
 
-        let mut deduped = dedupe_similar(scored);
+        let (mut deduped, merged) = dedupe_similar(scored);
         deduped.sort_by(|a, b| b.1.total.partial_cmp(&a.1.total).unwrap_or(std::cmp::Ordering::Equal));
-        deduped.into_iter().map(|(b, _)| b).collect()
+        Ranked {
+            bundles: deduped.into_iter().map(|(b, _)| b).collect(),
+            merged,
+        }
     }
 
-    fn score_bundle(&self, bundle: &IdeaBundle) -> ScoreCard {
+    /// Like `rank_and_merge`, but every persona keeps at least one bundle: a persona's only bundle is
+    /// never dropped as a duplicate, and the best bundle of each persona comes first (best persona
+    /// first), followed by the rest by score. Any top-k with k at least the persona count covers
+    /// every persona.
+    pub fn rank_covering_personas(&self, bundles: Vec<IdeaBundle>) -> Ranked {
+        let mut scored: Vec<(IdeaBundle, ScoreCard)> = bundles
+            .into_iter()
+            .map(|b| {
+                let card = self.score_bundle(&b);
+                (b, card)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total.partial_cmp(&a.1.total).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut leaders: Vec<(IdeaBundle, ScoreCard)> = Vec::new();
+        let mut rest: Vec<(IdeaBundle, ScoreCard)> = Vec::new();
+        for (bundle, card) in scored {
+            if !leaders.iter().any(|(l, _)| l.persona == bundle.persona) {
+                leaders.push((bundle, card));
+            } else {
+                rest.push((bundle, card));
+            }
+        }
+        let mut ranked = Ranked {
+            bundles: leaders.into_iter().map(|(b, _)| b).collect(),
+            merged: Vec::new(),
+        };
+        for (bundle, _) in rest {
+            match ranked.bundles.iter().find(|kept| is_similar(kept, &bundle)) {
+                Some(kept) => {
+                    let kept_id = kept.id.clone();
+                    ranked.merged.push((bundle, kept_id));
+                }
+                None => ranked.bundles.push(bundle),
+            }
+        }
+        ranked
+    }
+
+    pub fn score_bundle(&self, bundle: &IdeaBundle) -> ScoreCard {
         let novelty = heuristic_novelty(bundle);
         let feasibility = heuristic_feasibility(bundle);
         let cost = heuristic_cost(bundle);
@@ -57,6 +111,31 @@ impl Ranker {
     }
 }
 
+/// Parse a stored rubric: either a 6-element array or a `{ novelty, feasibility, ... }` object.
+/// Missing object keys fall back to the default weights.
+pub fn parse_rubric(json: &str) -> Option<[f64; 6]> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    match value {
+        serde_json::Value::Array(items) if items.len() == 6 => {
+            let mut rubric = [0.0; 6];
+            for (slot, item) in rubric.iter_mut().zip(items.iter()) {
+                *slot = item.as_f64()?;
+            }
+            Some(rubric)
+        }
+        serde_json::Value::Object(map) => {
+            let mut rubric = DEFAULT_RUBRIC;
+            for (slot, key) in rubric.iter_mut().zip(RUBRIC_KEYS) {
+                if let Some(weight) = map.get(key).and_then(|v| v.as_f64()) {
+                    *slot = weight;
+                }
+            }
+            Some(rubric)
+        }
+        _ => None,
+    }
+}
+
 fn heuristic_novelty(bundle: &IdeaBundle) -> f64 {
     let idea_count = bundle.ideas.len() as f64;
     let has_rationale = bundle.ideas.iter().filter(|i| i.rationale.is_some()).count() as f64;
@@ -86,11 +165,11 @@ fn heuristic_time(bundle: &IdeaBundle) -> f64 {
 }
 
 fn heuristic_risk(bundle: &IdeaBundle) -> f64 {
-    let high = bundle.risks.iter().filter(|r| r.severity == "high").count();
-    let med = bundle.risks.iter().filter(|r| r.severity == "medium").count();
+    let high = bundle.risks.iter().filter(|r| r.severity == Severity::High).count();
+    let med = bundle.risks.iter().filter(|r| r.severity == Severity::Medium).count();
     let with_mitigation = bundle.risks.iter().filter(|r| r.mitigation.is_some()).count();
     let base = 10.0 - (high as f64 * 2.0) - (med as f64 * 0.5);
-    (base + (with_mitigation as f64 * 0.3)).max(0.0).min(10.0)
+    (base + (with_mitigation as f64 * 0.3)).clamp(0.0, 10.0)
 }
 
 fn heuristic_clarity(bundle: &IdeaBundle) -> f64 {
@@ -100,19 +179,27 @@ fn heuristic_clarity(bundle: &IdeaBundle) -> f64 {
     (total as f64 / 10.0).min(10.0)
 }
 
-fn dedupe_similar(bundles: Vec<(IdeaBundle, ScoreCard)>) -> Vec<(IdeaBundle, ScoreCard)> {
-    let mut result = Vec::new();
+/// Keep the first of each group of similar bundles; the rest come back with the id they duplicate
+fn dedupe_similar(bundles: Vec<Scored>) -> (Vec<Scored>, Vec<(IdeaBundle, String)>) {
+    let mut result: Vec<Scored> = Vec::new();
+    let mut merged = Vec::new();
     for (bundle, card) in bundles {
-        let is_dup = result.iter().any(|(existing, _): &(IdeaBundle, ScoreCard)| {
-            let a_titles: Vec<_> = existing.ideas.iter().map(|i| i.title.as_str()).collect();
-            let b_titles: Vec<_> = bundle.ideas.iter().map(|i| i.title.as_str()).collect();
-            jaccard_similarity(&a_titles, &b_titles) > 0.7
-        });
-        if !is_dup {
-            result.push((bundle, card));
+        match result.iter().find(|(existing, _)| is_similar(existing, &bundle)) {
+            Some((existing, _)) => {
+                let existing_id = existing.id.clone();
+                merged.push((bundle, existing_id));
+            }
+            None => result.push((bundle, card)),
         }
     }
-    result
+    (result, merged)
+}
+
+/// Bundles whose idea titles mostly overlap
+fn is_similar(a: &IdeaBundle, b: &IdeaBundle) -> bool {
+    let a_titles: Vec<_> = a.ideas.iter().map(|i| i.title.as_str()).collect();
+    let b_titles: Vec<_> = b.ideas.iter().map(|i| i.title.as_str()).collect();
+    jaccard_similarity(&a_titles, &b_titles) > 0.7
 }
 
 fn jaccard_similarity(a: &[&str], b: &[&str]) -> f64 {
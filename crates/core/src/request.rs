@@ -0,0 +1,161 @@
+//! Completion requests - the conversation, generation parameters and response format sent to an adapter
+
+use serde::{Deserialize, Serialize};
+
+/// Sampling temperature when a request does not set one
+pub const DEFAULT_TEMPERATURE: f64 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// Sampling and length controls. Unset values leave the adapter defaults
+/// (temperature 0.7, 4096 output tokens, provider defaults for the rest).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl GenerationParams {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Parse a recipe's `params_json`; empty or missing means defaults. Unknown keys and values no
+    /// provider accepts are errors; per-provider limits are checked when a call is made.
+    pub fn from_json(json: Option<&str>) -> Result<Self, String> {
+        let Some(json) = json.map(str::trim).filter(|s| !s.is_empty()) else {
+            return Ok(Self::default());
+        };
+        let params: Self = serde_json::from_str(json).map_err(|e| format!("Invalid generation params: {}", e))?;
+        if params.temperature.is_some_and(|t| !(0.0..=2.0).contains(&t)) {
+            return Err("Invalid generation params: temperature must be between 0 and 2".to_string());
+        }
+        if params.top_p.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+            return Err("Invalid generation params: top_p must be between 0 and 1".to_string());
+        }
+        if params.max_tokens == Some(0) {
+            return Err("Invalid generation params: max_tokens must be positive".to_string());
+        }
+        Ok(params)
+    }
+
+    /// These params with unset fields taken from `base`
+    pub fn merged_over(&self, base: &GenerationParams) -> GenerationParams {
+        GenerationParams {
+            temperature: self.temperature.or(base.temperature),
+            max_tokens: self.max_tokens.or(base.max_tokens),
+            seed: self.seed.or(base.seed),
+            top_p: self.top_p.or(base.top_p),
+            stop: if self.stop.is_empty() { base.stop.clone() } else { self.stop.clone() },
+        }
+    }
+
+    pub fn temperature(&self) -> f64 {
+        self.temperature.unwrap_or(DEFAULT_TEMPERATURE)
+    }
+}
+
+/// How `complete` asks for a bundle. `complete_text` always asks for plain text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Provider-native structured output: OpenAI strict `json_schema`, Gemini `responseSchema`,
+    /// Anthropic forced tool use
+    #[default]
+    Schema,
+    /// JSON mode where the provider has one, with the schema in the system prompt
+    Json,
+    /// No output constraint; the schema is in the system prompt and the reply is parsed leniently
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub system_prompt: String,
+    /// The conversation so far, oldest first, ending with the user turn to answer
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub params: GenerationParams,
+    #[serde(default)]
+    pub response_format: ResponseFormat,
+}
+
+impl CompletionRequest {
+    /// A single-turn request
+    pub fn new(system_prompt: &str, user_prompt: &str) -> Self {
+        Self {
+            system_prompt: system_prompt.to_string(),
+            messages: vec![Message::user(user_prompt)],
+            params: GenerationParams::default(),
+            response_format: ResponseFormat::default(),
+        }
+    }
+
+    /// Put earlier turns of the conversation before the current one
+    pub fn with_history(mut self, history: Vec<Message>) -> Self {
+        self.messages.splice(0..0, history);
+        self
+    }
+
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = format;
+        self
+    }
+
+    /// The last user turn - the one being answered
+    pub fn user_prompt(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::User)
+            .map(|m| m.content.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Every turn before the last user turn
+    pub fn history(&self) -> &[Message] {
+        let last_user = self.messages.iter().rposition(|m| m.role == Role::User).unwrap_or(0);
+        &self.messages[..last_user]
+    }
+}
@@ -0,0 +1,113 @@
+//! Response JSON Schema - derived once from `AIResponse`, adapted to each provider's dialect
+
+use crate::schema::AIResponse;
+use schemars::generate::SchemaSettings;
+use serde_json::{json, Map, Value};
+
+/// Bump when the shape of `AIResponse` changes; it names the exported artifact
+pub const RESPONSE_SCHEMA_VERSION: u32 = 1;
+
+/// Name used for the OpenAI `json_schema` response format and the Anthropic tool
+pub const RESPONSE_SCHEMA_NAME: &str = "idea_response";
+
+/// Keywords Gemini's OpenAPI-subset `responseSchema` accepts; everything else is dropped
+const GEMINI_KEYWORDS: [&str; 7] = ["type", "nullable", "enum", "properties", "required", "items", "description"];
+
+/// Canonical draft-07 schema with every subschema inlined. This is the exported artifact.
+pub fn response_schema() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|s| s.inline_subschemas = true)
+        .into_generator();
+    let mut schema = generator.into_root_schema_for::<AIResponse>().to_value();
+    schema["$id"] = json!(format!("idea-engine/ai-response/v{}", RESPONSE_SCHEMA_VERSION));
+    schema
+}
+
+/// OpenAI strict mode: every property required (optional ones are nullable), no extra properties
+pub fn openai_strict_schema() -> Value {
+    let mut schema = provider_body();
+    strict(&mut schema);
+    schema
+}
+
+/// Gemini `responseSchema`: OpenAPI subset - `nullable` instead of null types, a short keyword list
+pub fn gemini_schema() -> Value {
+    gemini(provider_body())
+}
+
+/// Anthropic tool `input_schema`: plain JSON Schema
+pub fn tool_input_schema() -> Value {
+    provider_body()
+}
+
+/// The canonical schema without the root metadata providers reject or ignore
+fn provider_body() -> Value {
+    let mut schema = response_schema();
+    if let Value::Object(root) = &mut schema {
+        for key in ["$schema", "$id", "title", "description"] {
+            root.remove(key);
+        }
+    }
+    schema
+}
+
+fn strict(schema: &mut Value) {
+    let Value::Object(obj) = schema else {
+        return;
+    };
+    obj.remove("format");
+    if let Some(Value::Object(properties)) = obj.get("properties") {
+        let required: Vec<Value> = properties.keys().map(|k| json!(k)).collect();
+        obj.insert("required".into(), Value::Array(required));
+        obj.insert("additionalProperties".into(), json!(false));
+    }
+    for child in children(obj) {
+        strict(child);
+    }
+}
+
+fn gemini(schema: Value) -> Value {
+    let Value::Object(mut obj) = schema else {
+        return schema;
+    };
+    if let Some(Value::Array(types)) = obj.get("type").cloned() {
+        let non_null: Vec<&Value> = types.iter().filter(|t| **t != json!("null")).collect();
+        if non_null.len() < types.len() {
+            obj.insert("nullable".into(), json!(true));
+        }
+        if let Some(first) = non_null.first() {
+            obj.insert("type".into(), (*first).clone());
+        }
+    }
+    if let Some(Value::Array(values)) = obj.get_mut("enum") {
+        values.retain(|v| !v.is_null());
+    }
+    let mut out = Map::new();
+    for (key, value) in obj {
+        if !GEMINI_KEYWORDS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match (key.as_str(), value) {
+            ("properties", Value::Object(props)) => {
+                Value::Object(props.into_iter().map(|(k, v)| (k, gemini(v))).collect())
+            }
+            ("items", items) => gemini(items),
+            (_, other) => other,
+        };
+        out.insert(key, value);
+    }
+    Value::Object(out)
+}
+
+/// Subschemas under `properties` and `items`
+fn children(obj: &mut Map<String, Value>) -> Vec<&mut Value> {
+    let mut out = Vec::new();
+    for (key, value) in obj.iter_mut() {
+        match (key.as_str(), value) {
+            ("properties", Value::Object(props)) => out.extend(props.values_mut()),
+            ("items", items) => out.push(items),
+            _ => {}
+        }
+    }
+    out
+}
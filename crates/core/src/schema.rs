@@ -1,6 +1,120 @@
 //! Internal IdeaBundle schema - matches @idea-engine/shared types
 
+use crate::request::GenerationParams;
+use crate::usage::Usage;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+/// A string enum that deserializes leniently through `$normalize` and keeps anything it cannot map
+/// in `Unknown`, so an odd provider value is visible instead of silently dropped.
+macro_rules! lenient_enum {
+    ($(#[$meta:meta])* $name:ident, $normalize:path, { $($variant:ident => $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(from = "String", into = "String")]
+        pub enum $name {
+            $($variant,)+
+            Unknown(String),
+        }
+
+        impl $name {
+            /// Canonical wire values, in order
+            pub const VALUES: &'static [&'static str] = &[$($value),+];
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $value,)+
+                    Self::Unknown(raw) => raw,
+                }
+            }
+
+            pub fn is_known(&self) -> bool {
+                !matches!(self, Self::Unknown(_))
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(raw: String) -> Self {
+                match $normalize(&raw) {
+                    $(Some($value) => Self::$variant,)+
+                    _ => Self::Unknown(raw.trim().to_string()),
+                }
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(raw: &str) -> Self {
+                Self::from(raw.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.as_str().to_string()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        /// Providers are asked for the canonical values only; `Unknown` exists for what they send anyway
+        impl JsonSchema for $name {
+            fn schema_name() -> Cow<'static, str> {
+                stringify!($name).into()
+            }
+
+            fn inline_schema() -> bool {
+                true
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                json_schema!({ "type": "string", "enum": Self::VALUES })
+            }
+        }
+    };
+}
+
+lenient_enum!(
+    /// Risk severity - "High", "critical", "minor" etc. map onto the three levels
+    Severity, normalize_level, { Low => "low", Medium => "medium", High => "high" }
+);
+
+lenient_enum!(
+    /// Next-action priority - "urgent", "short-term", "later" etc. map onto the four horizons
+    Priority, normalize_priority, { Immediate => "immediate", Short => "short", Medium => "medium", Long => "long" }
+);
+
+lenient_enum!(
+    /// Effort complexity, same vocabulary as severity
+    Complexity, normalize_level, { Low => "low", Medium => "medium", High => "high" }
+);
+
+/// Lowercase and map a severity or complexity onto low / medium / high
+pub fn normalize_level(raw: &str) -> Option<&'static str> {
+    match raw.trim().to_lowercase().replace(['-', '_'], " ").as_str() {
+        "low" | "minor" | "minimal" | "negligible" | "trivial" | "small" | "easy" => Some("low"),
+        "medium" | "med" | "moderate" | "mid" | "normal" | "average" => Some("medium"),
+        "high" | "critical" | "severe" | "very high" | "major" | "blocker" | "large" | "hard" | "complex" => Some("high"),
+        _ => None,
+    }
+}
+
+/// Lowercase and map a priority onto immediate / short / medium / long. Priorities are timeframes, so
+/// importance words such as "high" or "low" are not mapped and stay unknown.
+pub fn normalize_priority(raw: &str) -> Option<&'static str> {
+    match raw.trim().to_lowercase().replace(['-', '_'], " ").as_str() {
+        "immediate" | "now" | "urgent" | "asap" | "today" | "p0" => Some("immediate"),
+        "short" | "short term" | "soon" | "this week" | "p1" => Some("short"),
+        "medium" | "medium term" | "normal" | "next" | "p2" => Some("medium"),
+        "long" | "long term" | "later" | "eventually" | "p3" => Some("long"),
+        _ => None,
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IdeaBundle {
@@ -15,10 +129,37 @@ pub struct IdeaBundle {
     pub next_actions: Vec<NextAction>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw_response: Option<String>,
+    /// Repairs and coercions applied to the provider output, plus any semantic violations left unfixed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Follow-up requests sent to fix semantic violations, whether or not they helped; 0 when the
+    /// first answer was valid or repair is off
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub repair_attempts: u32,
+    /// Generation parameters the provider was called with, for reproducing the bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<GenerationParams>,
+    /// Id of the persona whose overlay the bundle was generated under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    /// Tokens, latency and cost of every call behind this bundle, retries and repairs included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
     pub created_at: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl IdeaBundle {
+    /// "provider:model", the key results are grouped by
+    pub fn target(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Idea {
     pub title: String,
     pub description: String,
@@ -26,39 +167,41 @@ pub struct Idea {
     pub rationale: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Step {
+    /// 1-based position in the plan
     pub order: i32,
     pub action: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Risk {
     pub description: String,
-    pub severity: String,
+    pub severity: Severity,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mitigation: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EffortEstimate {
+    /// Rough duration, e.g. "2 weeks"
     pub time: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cost: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub complexity: Option<String>,
+    pub complexity: Option<Complexity>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct NextAction {
     pub action: String,
-    pub priority: String,
+    pub priority: Priority,
 }
 
 /// Raw AI response schema - what we expect from providers
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AIResponse {
     pub ideas: Vec<Idea>,
     pub step_plan: Vec<Step>,
@@ -1,9 +1,13 @@
-//! SQLite persistence for chats, recipes, scores, feedback
+//! SQLite persistence for chats, recipes, scores, feedback, usage
 
+use crate::schema::IdeaBundle;
+use crate::usage::{Attempt, Usage};
 use rusqlite::{params, Connection, Result as SqlResult};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::Mutex;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatRow {
@@ -22,9 +26,24 @@ pub struct MessageRow {
     pub content: String,
     pub idea_bundles_json: Option<String>,
     pub feedback: Option<String>,
+    /// Set on drill-down messages: the idea they expand
+    #[serde(default)]
+    pub parent: Option<IdeaRef>,
+    /// Set on decomposed answers: the `HierarchicalPlan` the bundles were assembled into
+    #[serde(default)]
+    pub plan_json: Option<String>,
     pub created_at: String,
 }
 
+/// One idea inside a stored message: the message, the bundle in its `idea_bundles_json`, and the
+/// idea's 0-based position in that bundle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdeaRef {
+    pub message_id: String,
+    pub bundle_id: String,
+    pub idea_index: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecipeRow {
     pub id: String,
@@ -33,9 +52,141 @@ pub struct RecipeRow {
     pub user_prompt_template: String,
     pub rubric_json: String,
     pub few_shot_examples_json: Option<String>,
+    #[serde(default)]
+    pub assertions_json: Option<String>,
+    /// `GenerationParams` as JSON; None means provider defaults
+    #[serde(default)]
+    pub params_json: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetRow {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalProblemRow {
+    pub id: String,
+    pub dataset_id: Option<String>,
+    pub content: String,
+    pub variables_json: Option<String>,
+    pub tags_json: Option<String>,
+    pub assertions_json: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalResultRow {
+    pub id: String,
+    pub run_id: String,
+    pub recipe_id: String,
+    pub problem_id: String,
+    pub bundle_id: String,
+    pub provider: String,
+    pub model: String,
+    pub score_card_json: String,
+    pub delta_json: Option<String>,
+    pub assertions_json: Option<String>,
+    /// Generation parameters the bundle was produced with
+    pub params_json: Option<String>,
+    pub created_at: String,
+}
+
+/// A provider that failed on one problem during an eval run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalFailureRow {
+    pub id: String,
+    pub run_id: String,
+    pub recipe_id: String,
+    pub problem_id: String,
+    pub provider: String,
+    pub error: String,
+    pub created_at: String,
+}
+
+/// One eval run as listed by `list_eval_runs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalRunRow {
+    pub run_id: String,
+    pub recipe_id: String,
+    pub result_count: i64,
+    pub mean_total: f64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalComparisonRow {
+    pub id: String,
+    pub label_a: String,
+    pub label_b: String,
+    pub b_beats_a: bool,
+    pub result_json: String,
     pub created_at: String,
 }
 
+/// One judged pairwise match between two contestants' bundles for the same problem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalMatchRow {
+    pub id: String,
+    pub tournament_id: String,
+    pub problem_id: String,
+    pub contestant_a: String,
+    pub contestant_b: String,
+    pub bundle_a_json: String,
+    pub bundle_b_json: String,
+    /// "a", "b" or "tie"
+    pub winner: String,
+    pub reason: String,
+    pub judge: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalRatingRow {
+    pub contestant: String,
+    pub elo: f64,
+    pub bradley_terry: f64,
+    pub matches: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub ties: i64,
+    pub updated_at: String,
+}
+
+/// What a run's usage is charged to; unset fields are stored as NULL
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageScope {
+    pub chat_id: Option<String>,
+    pub message_id: Option<String>,
+    pub recipe_id: Option<String>,
+    /// Eval run or tournament id
+    pub run_id: Option<String>,
+}
+
+/// How `spend` groups usage rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpendGroup {
+    /// UTC date, "2024-05-01"
+    Day,
+    Provider,
+    /// "provider:model"
+    Model,
+    Recipe,
+    Chat,
+}
+
+/// Usage summed over one group; `key` is None for rows without a recipe or chat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendRow {
+    pub key: Option<String>,
+    pub usage: Usage,
+}
+
 pub struct Storage {
     conn: Mutex<Connection>,
 }
@@ -86,10 +237,100 @@ impl Storage {
                 score_card_json TEXT NOT NULL,
                 created_at TEXT NOT NULL
             );
+            CREATE TABLE IF NOT EXISTS eval_failures (
+                id TEXT PRIMARY KEY,
+                run_id TEXT NOT NULL,
+                recipe_id TEXT NOT NULL,
+                problem_id TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                error TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS eval_datasets (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                description TEXT,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS eval_problems (
+                id TEXT PRIMARY KEY,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS eval_comparisons (
+                id TEXT PRIMARY KEY,
+                label_a TEXT NOT NULL,
+                label_b TEXT NOT NULL,
+                b_beats_a INTEGER NOT NULL,
+                result_json TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS eval_matches (
+                id TEXT PRIMARY KEY,
+                tournament_id TEXT NOT NULL,
+                problem_id TEXT NOT NULL,
+                contestant_a TEXT NOT NULL,
+                contestant_b TEXT NOT NULL,
+                bundle_a_json TEXT NOT NULL,
+                bundle_b_json TEXT NOT NULL,
+                winner TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                judge TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS eval_ratings (
+                contestant TEXT PRIMARY KEY,
+                elo REAL NOT NULL,
+                bradley_terry REAL NOT NULL,
+                matches INTEGER NOT NULL,
+                wins INTEGER NOT NULL,
+                losses INTEGER NOT NULL,
+                ties INTEGER NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS usage (
+                id TEXT PRIMARY KEY,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                input_tokens INTEGER NOT NULL,
+                output_tokens INTEGER NOT NULL,
+                latency_ms INTEGER NOT NULL,
+                cost_usd REAL NOT NULL,
+                attempts INTEGER NOT NULL,
+                unpriced_attempts INTEGER NOT NULL,
+                chat_id TEXT,
+                message_id TEXT,
+                recipe_id TEXT,
+                run_id TEXT,
+                created_at TEXT NOT NULL
+            );
             CREATE INDEX IF NOT EXISTS idx_messages_chat ON messages(chat_id);
+            CREATE INDEX IF NOT EXISTS idx_matches_tournament ON eval_matches(tournament_id);
             CREATE INDEX IF NOT EXISTS idx_eval_recipe ON eval_results(recipe_id);
+            CREATE INDEX IF NOT EXISTS idx_failures_run ON eval_failures(run_id);
             "#,
         )?;
+        // Columns added after the first release - older databases get them here
+        ensure_column(conn, "eval_results", "run_id", "TEXT NOT NULL DEFAULT ''")?;
+        ensure_column(conn, "eval_results", "provider", "TEXT NOT NULL DEFAULT ''")?;
+        ensure_column(conn, "eval_results", "model", "TEXT NOT NULL DEFAULT ''")?;
+        ensure_column(conn, "eval_results", "delta_json", "TEXT")?;
+        ensure_column(conn, "eval_problems", "dataset_id", "TEXT REFERENCES eval_datasets(id)")?;
+        ensure_column(conn, "eval_problems", "variables_json", "TEXT")?;
+        ensure_column(conn, "eval_problems", "tags_json", "TEXT")?;
+        ensure_column(conn, "eval_problems", "assertions_json", "TEXT")?;
+        ensure_column(conn, "eval_results", "assertions_json", "TEXT")?;
+        ensure_column(conn, "recipes", "assertions_json", "TEXT")?;
+        ensure_column(conn, "recipes", "params_json", "TEXT")?;
+        ensure_column(conn, "messages", "parent_message_id", "TEXT REFERENCES messages(id)")?;
+        ensure_column(conn, "messages", "parent_bundle_id", "TEXT")?;
+        ensure_column(conn, "messages", "parent_idea_index", "INTEGER")?;
+        ensure_column(conn, "eval_results", "params_json", "TEXT")?;
+        ensure_column(conn, "messages", "plan_json", "TEXT")?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_eval_problem ON eval_results(problem_id, recipe_id);
+             CREATE INDEX IF NOT EXISTS idx_problems_dataset ON eval_problems(dataset_id);",
+        )?;
         Ok(())
     }
 
@@ -123,22 +364,21 @@ impl Storage {
     pub fn get_chat_messages(&self, chat_id: &str) -> SqlResult<Vec<MessageRow>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, chat_id, role, content, idea_bundles_json, feedback, created_at FROM messages WHERE chat_id = ?1 ORDER BY created_at ASC",
+            "SELECT id, chat_id, role, content, idea_bundles_json, feedback, created_at, parent_message_id, parent_bundle_id, parent_idea_index, plan_json FROM messages WHERE chat_id = ?1 ORDER BY CAST(created_at AS INTEGER) ASC, rowid ASC",
         )?;
-        let rows = stmt.query_map(params![chat_id], |row| {
-            Ok(MessageRow {
-                id: row.get(0)?,
-                chat_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                idea_bundles_json: row.get(4)?,
-                feedback: row.get(5)?,
-                created_at: row.get(6)?,
-            })
-        })?;
+        let rows = stmt.query_map(params![chat_id], map_message)?;
         rows.collect()
     }
 
+    pub fn get_message(&self, id: &str) -> SqlResult<Option<MessageRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, chat_id, role, content, idea_bundles_json, feedback, created_at, parent_message_id, parent_bundle_id, parent_idea_index, plan_json FROM messages WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map(params![id], map_message)?;
+        rows.next().transpose()
+    }
+
     pub fn insert_message(
         &self,
         id: &str,
@@ -146,12 +386,35 @@ impl Storage {
         role: &str,
         content: &str,
         idea_bundles_json: Option<&str>,
+    ) -> SqlResult<()> {
+        self.insert_child_message(id, chat_id, role, content, idea_bundles_json, None)
+    }
+
+    /// Insert a message, linked to the idea it expands when `parent` is set
+    pub fn insert_child_message(
+        &self,
+        id: &str,
+        chat_id: &str,
+        role: &str,
+        content: &str,
+        idea_bundles_json: Option<&str>,
+        parent: Option<&IdeaRef>,
     ) -> SqlResult<()> {
         let now = chrono_utc_now();
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO messages (id, chat_id, role, content, idea_bundles_json, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![id, chat_id, role, content, idea_bundles_json, now],
+            "INSERT INTO messages (id, chat_id, role, content, idea_bundles_json, created_at, parent_message_id, parent_bundle_id, parent_idea_index) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                id,
+                chat_id,
+                role,
+                content,
+                idea_bundles_json,
+                now,
+                parent.map(|p| &p.message_id),
+                parent.map(|p| &p.bundle_id),
+                parent.map(|p| p.idea_index as i64),
+            ],
         )?;
         conn.execute("UPDATE chats SET updated_at = ?1 WHERE id = ?2", params![now, chat_id])?;
         Ok(())
@@ -163,10 +426,17 @@ impl Storage {
         Ok(())
     }
 
+    /// Attach the serialized plan of a decomposed answer to its message
+    pub fn set_message_plan(&self, message_id: &str, plan_json: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE messages SET plan_json = ?1 WHERE id = ?2", params![plan_json, message_id])?;
+        Ok(())
+    }
+
     pub fn save_recipe(&self, recipe: &RecipeRow) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT OR REPLACE INTO recipes (id, name, system_prompt, user_prompt_template, rubric_json, few_shot_examples_json, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT OR REPLACE INTO recipes (id, name, system_prompt, user_prompt_template, rubric_json, few_shot_examples_json, assertions_json, params_json, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
                 recipe.id,
                 recipe.name,
@@ -174,6 +444,8 @@ impl Storage {
                 recipe.user_prompt_template,
                 recipe.rubric_json,
                 recipe.few_shot_examples_json,
+                recipe.assertions_json,
+                recipe.params_json,
                 recipe.created_at,
             ],
         )?;
@@ -183,7 +455,7 @@ impl Storage {
     pub fn list_recipes(&self) -> SqlResult<Vec<RecipeRow>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, system_prompt, user_prompt_template, rubric_json, few_shot_examples_json, created_at FROM recipes ORDER BY name",
+            "SELECT id, name, system_prompt, user_prompt_template, rubric_json, few_shot_examples_json, assertions_json, params_json, created_at FROM recipes ORDER BY name",
         )?;
         let rows = stmt.query_map([], |row| {
             Ok(RecipeRow {
@@ -193,14 +465,484 @@ impl Storage {
                 user_prompt_template: row.get(3)?,
                 rubric_json: row.get(4)?,
                 few_shot_examples_json: row.get(5)?,
+                assertions_json: row.get(6)?,
+                params_json: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn get_recipe(&self, id: &str) -> SqlResult<Option<RecipeRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, system_prompt, user_prompt_template, rubric_json, few_shot_examples_json, assertions_json, params_json, created_at FROM recipes WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map(params![id], |row| {
+            Ok(RecipeRow {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                system_prompt: row.get(2)?,
+                user_prompt_template: row.get(3)?,
+                rubric_json: row.get(4)?,
+                few_shot_examples_json: row.get(5)?,
+                assertions_json: row.get(6)?,
+                params_json: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?;
+        rows.next().transpose()
+    }
+
+    pub fn create_dataset(&self, id: &str, name: &str, description: Option<&str>) -> SqlResult<()> {
+        let now = chrono_utc_now();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO eval_datasets (id, name, description, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![id, name, description, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_dataset_by_name(&self, name: &str) -> SqlResult<Option<DatasetRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, description, created_at FROM eval_datasets WHERE name = ?1")?;
+        let mut rows = stmt.query_map(params![name], map_dataset)?;
+        rows.next().transpose()
+    }
+
+    pub fn list_datasets(&self) -> SqlResult<Vec<DatasetRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, description, created_at FROM eval_datasets ORDER BY name")?;
+        let rows = stmt.query_map([], map_dataset)?;
+        rows.collect()
+    }
+
+    pub fn save_eval_problem(&self, problem: &EvalProblemRow) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO eval_problems (id, dataset_id, content, variables_json, tags_json, assertions_json, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                problem.id,
+                problem.dataset_id,
+                problem.content,
+                problem.variables_json,
+                problem.tags_json,
+                problem.assertions_json,
+                problem.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns each requested problem that exists, in request order
+    pub fn get_eval_problems(&self, ids: &[String]) -> SqlResult<Vec<EvalProblemRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, dataset_id, content, variables_json, tags_json, assertions_json, created_at FROM eval_problems WHERE id = ?1",
+        )?;
+        let mut problems = Vec::with_capacity(ids.len());
+        for id in ids {
+            let mut rows = stmt.query_map(params![id], map_eval_problem)?;
+            if let Some(problem) = rows.next().transpose()? {
+                problems.push(problem);
+            }
+        }
+        Ok(problems)
+    }
+
+    pub fn list_dataset_problems(&self, dataset_id: &str) -> SqlResult<Vec<EvalProblemRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, dataset_id, content, variables_json, tags_json, assertions_json, created_at FROM eval_problems WHERE dataset_id = ?1 ORDER BY created_at ASC, rowid ASC",
+        )?;
+        let rows = stmt.query_map(params![dataset_id], map_eval_problem)?;
+        rows.collect()
+    }
+
+    pub fn insert_eval_result(&self, row: &EvalResultRow) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO eval_results (id, run_id, recipe_id, problem_id, bundle_id, provider, model, score_card_json, delta_json, assertions_json, params_json, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                row.id,
+                row.run_id,
+                row.recipe_id,
+                row.problem_id,
+                row.bundle_id,
+                row.provider,
+                row.model,
+                row.score_card_json,
+                row.delta_json,
+                row.assertions_json,
+                row.params_json,
+                row.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Most recent result for the same recipe, problem, provider and model, ignoring `exclude_run_id`
+    pub fn latest_eval_result(
+        &self,
+        recipe_id: &str,
+        problem_id: &str,
+        provider: &str,
+        model: &str,
+        exclude_run_id: &str,
+    ) -> SqlResult<Option<EvalResultRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, recipe_id, problem_id, bundle_id, provider, model, score_card_json, delta_json, assertions_json, params_json, created_at FROM eval_results WHERE recipe_id = ?1 AND problem_id = ?2 AND provider = ?3 AND model = ?4 AND run_id != ?5 ORDER BY CAST(created_at AS INTEGER) DESC, rowid DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(params![recipe_id, problem_id, provider, model, exclude_run_id], map_eval_result)?;
+        rows.next().transpose()
+    }
+
+    pub fn get_eval_run(&self, run_id: &str) -> SqlResult<Vec<EvalResultRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, recipe_id, problem_id, bundle_id, provider, model, score_card_json, delta_json, assertions_json, params_json, created_at FROM eval_results WHERE run_id = ?1 ORDER BY rowid ASC",
+        )?;
+        let rows = stmt.query_map(params![run_id], map_eval_result)?;
+        rows.collect()
+    }
+
+    pub fn insert_eval_failure(&self, row: &EvalFailureRow) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO eval_failures (id, run_id, recipe_id, problem_id, provider, error, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![row.id, row.run_id, row.recipe_id, row.problem_id, row.provider, row.error, row.created_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_eval_failures(&self, run_id: &str) -> SqlResult<Vec<EvalFailureRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, recipe_id, problem_id, provider, error, created_at FROM eval_failures WHERE run_id = ?1 ORDER BY rowid ASC",
+        )?;
+        let rows = stmt.query_map(params![run_id], |row| {
+            Ok(EvalFailureRow {
+                id: row.get(0)?,
+                run_id: row.get(1)?,
+                recipe_id: row.get(2)?,
+                problem_id: row.get(3)?,
+                provider: row.get(4)?,
+                error: row.get(5)?,
                 created_at: row.get(6)?,
             })
         })?;
         rows.collect()
     }
+
+    /// Eval runs, newest first, optionally limited to one recipe
+    pub fn list_eval_runs(&self, recipe_id: Option<&str>) -> SqlResult<Vec<EvalRunRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT run_id, recipe_id, COUNT(*), AVG(json_extract(score_card_json, '$.total')), MIN(created_at) FROM eval_results WHERE run_id != '' AND (?1 IS NULL OR recipe_id = ?1) GROUP BY run_id, recipe_id ORDER BY MIN(CAST(created_at AS INTEGER)) DESC, MIN(rowid) DESC",
+        )?;
+        let rows = stmt.query_map(params![recipe_id], |row| {
+            Ok(EvalRunRow {
+                run_id: row.get(0)?,
+                recipe_id: row.get(1)?,
+                result_count: row.get(2)?,
+                mean_total: row.get::<_, Option<f64>>(3)?.unwrap_or_default(),
+                created_at: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn save_eval_comparison(&self, row: &EvalComparisonRow) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO eval_comparisons (id, label_a, label_b, b_beats_a, result_json, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![row.id, row.label_a, row.label_b, row.b_beats_a, row.result_json, row.created_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_eval_comparison(&self, id: &str) -> SqlResult<Option<EvalComparisonRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, label_a, label_b, b_beats_a, result_json, created_at FROM eval_comparisons WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map(params![id], map_eval_comparison)?;
+        rows.next().transpose()
+    }
+
+    pub fn list_eval_comparisons(&self) -> SqlResult<Vec<EvalComparisonRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, label_a, label_b, b_beats_a, result_json, created_at FROM eval_comparisons ORDER BY CAST(created_at AS INTEGER) DESC, rowid DESC",
+        )?;
+        let rows = stmt.query_map([], map_eval_comparison)?;
+        rows.collect()
+    }
+
+    pub fn insert_eval_match(&self, row: &EvalMatchRow) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO eval_matches (id, tournament_id, problem_id, contestant_a, contestant_b, bundle_a_json, bundle_b_json, winner, reason, judge, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                row.id,
+                row.tournament_id,
+                row.problem_id,
+                row.contestant_a,
+                row.contestant_b,
+                row.bundle_a_json,
+                row.bundle_b_json,
+                row.winner,
+                row.reason,
+                row.judge,
+                row.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Matches in the order they were played, optionally limited to one tournament
+    pub fn list_eval_matches(&self, tournament_id: Option<&str>) -> SqlResult<Vec<EvalMatchRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, tournament_id, problem_id, contestant_a, contestant_b, bundle_a_json, bundle_b_json, winner, reason, judge, created_at FROM eval_matches WHERE ?1 IS NULL OR tournament_id = ?1 ORDER BY CAST(created_at AS INTEGER), rowid",
+        )?;
+        let rows = stmt.query_map(params![tournament_id], map_eval_match)?;
+        rows.collect()
+    }
+
+    /// Replace the stored leaderboard with freshly computed ratings
+    pub fn replace_eval_ratings(&self, ratings: &[EvalRatingRow]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM eval_ratings", [])?;
+        for r in ratings {
+            tx.execute(
+                "INSERT INTO eval_ratings (contestant, elo, bradley_terry, matches, wins, losses, ties, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![r.contestant, r.elo, r.bradley_terry, r.matches, r.wins, r.losses, r.ties, r.updated_at],
+            )?;
+        }
+        tx.commit()
+    }
+
+    pub fn list_eval_ratings(&self) -> SqlResult<Vec<EvalRatingRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT contestant, elo, bradley_terry, matches, wins, losses, ties, updated_at FROM eval_ratings ORDER BY elo DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(EvalRatingRow {
+                contestant: row.get(0)?,
+                elo: row.get(1)?,
+                bradley_terry: row.get(2)?,
+                matches: row.get(3)?,
+                wins: row.get(4)?,
+                losses: row.get(5)?,
+                ties: row.get(6)?,
+                updated_at: row.get(7)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Store a run's attempts, one row per provider:model
+    pub fn record_usage(&self, attempts: &[Attempt], scope: &UsageScope) -> SqlResult<()> {
+        let mut by_target: BTreeMap<(&str, &str), Usage> = BTreeMap::new();
+        for attempt in attempts {
+            by_target
+                .entry((attempt.provider.as_str(), attempt.model.as_str()))
+                .or_default()
+                .add(attempt);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = chrono_utc_now();
+        for ((provider, model), u) in by_target {
+            tx.execute(
+                "INSERT INTO usage (id, provider, model, input_tokens, output_tokens, latency_ms, cost_usd, attempts, unpriced_attempts, chat_id, message_id, recipe_id, run_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    provider,
+                    model,
+                    u.input_tokens as i64,
+                    u.output_tokens as i64,
+                    u.latency_ms as i64,
+                    u.cost_usd,
+                    u.attempts,
+                    u.unpriced_attempts,
+                    scope.chat_id,
+                    scope.message_id,
+                    scope.recipe_id,
+                    scope.run_id,
+                    now,
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Usage summed per group, largest cost first; `since` is a unix timestamp in seconds
+    pub fn spend(&self, group: SpendGroup, since: Option<i64>) -> SqlResult<Vec<SpendRow>> {
+        let key = match group {
+            SpendGroup::Day => "date(CAST(created_at AS INTEGER), 'unixepoch')",
+            SpendGroup::Provider => "provider",
+            SpendGroup::Model => "provider || ':' || model",
+            SpendGroup::Recipe => "recipe_id",
+            SpendGroup::Chat => "chat_id",
+        };
+        let order = if group == SpendGroup::Day { "key DESC" } else { "SUM(cost_usd) DESC" };
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} AS key, SUM(input_tokens), SUM(output_tokens), SUM(latency_ms), SUM(cost_usd), SUM(attempts), SUM(unpriced_attempts) FROM usage WHERE ?1 IS NULL OR CAST(created_at AS INTEGER) >= ?1 GROUP BY key ORDER BY {}",
+            key, order
+        ))?;
+        let rows = stmt.query_map(params![since], |row| {
+            Ok(SpendRow {
+                key: row.get(0)?,
+                usage: Usage {
+                    input_tokens: row.get::<_, i64>(1)? as u64,
+                    output_tokens: row.get::<_, i64>(2)? as u64,
+                    latency_ms: row.get::<_, i64>(3)? as u64,
+                    cost_usd: row.get(4)?,
+                    attempts: row.get(5)?,
+                    unpriced_attempts: row.get(6)?,
+                },
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+fn map_eval_match(row: &rusqlite::Row<'_>) -> SqlResult<EvalMatchRow> {
+    Ok(EvalMatchRow {
+        id: row.get(0)?,
+        tournament_id: row.get(1)?,
+        problem_id: row.get(2)?,
+        contestant_a: row.get(3)?,
+        contestant_b: row.get(4)?,
+        bundle_a_json: row.get(5)?,
+        bundle_b_json: row.get(6)?,
+        winner: row.get(7)?,
+        reason: row.get(8)?,
+        judge: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+fn map_eval_comparison(row: &rusqlite::Row<'_>) -> SqlResult<EvalComparisonRow> {
+    Ok(EvalComparisonRow {
+        id: row.get(0)?,
+        label_a: row.get(1)?,
+        label_b: row.get(2)?,
+        b_beats_a: row.get(3)?,
+        result_json: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn map_dataset(row: &rusqlite::Row<'_>) -> SqlResult<DatasetRow> {
+    Ok(DatasetRow {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+fn map_eval_problem(row: &rusqlite::Row<'_>) -> SqlResult<EvalProblemRow> {
+    Ok(EvalProblemRow {
+        id: row.get(0)?,
+        dataset_id: row.get(1)?,
+        content: row.get(2)?,
+        variables_json: row.get(3)?,
+        tags_json: row.get(4)?,
+        assertions_json: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Bundles in the camelCase shape stored in `messages.idea_bundles_json` and read by the frontend
+pub fn message_bundles(bundles: &[IdeaBundle]) -> serde_json::Value {
+    let bundles: Vec<serde_json::Value> = bundles
+        .iter()
+        .map(|b| {
+            serde_json::json!({
+                "id": b.id,
+                "provider": b.provider,
+                "model": b.model,
+                "ideas": b.ideas,
+                "stepPlan": b.step_plan,
+                "risks": b.risks,
+                "dependencies": b.dependencies,
+                "effort": b.effort,
+                "nextActions": b.next_actions,
+                "warnings": b.warnings,
+                "repairAttempts": b.repair_attempts,
+                "params": b.params,
+                "persona": b.persona,
+                "usage": b.usage,
+                "createdAt": b.created_at,
+            })
+        })
+        .collect();
+    serde_json::Value::Array(bundles)
+}
+
+fn map_message(row: &rusqlite::Row<'_>) -> SqlResult<MessageRow> {
+    let parent_message_id: Option<String> = row.get(7)?;
+    let parent_bundle_id: Option<String> = row.get(8)?;
+    let parent_idea_index: Option<i64> = row.get(9)?;
+    Ok(MessageRow {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        idea_bundles_json: row.get(4)?,
+        feedback: row.get(5)?,
+        parent: match (parent_message_id, parent_bundle_id, parent_idea_index) {
+            (Some(message_id), Some(bundle_id), Some(idea_index)) => Some(IdeaRef {
+                message_id,
+                bundle_id,
+                idea_index: idea_index as usize,
+            }),
+            _ => None,
+        },
+        plan_json: row.get(10)?,
+        created_at: row.get(6)?,
+    })
+}
+
+fn map_eval_result(row: &rusqlite::Row<'_>) -> SqlResult<EvalResultRow> {
+    Ok(EvalResultRow {
+        id: row.get(0)?,
+        run_id: row.get(1)?,
+        recipe_id: row.get(2)?,
+        problem_id: row.get(3)?,
+        bundle_id: row.get(4)?,
+        provider: row.get(5)?,
+        model: row.get(6)?,
+        score_card_json: row.get(7)?,
+        delta_json: row.get(8)?,
+        assertions_json: row.get(9)?,
+        params_json: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> SqlResult<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+    }
+    Ok(())
 }
 
-fn chrono_utc_now() -> String {
+pub(crate) fn chrono_utc_now() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
     let secs = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -0,0 +1,96 @@
+//! Usage - tokens, latency and cost of provider calls, collected per task while a run is in flight
+
+use crate::catalog;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// One provider call that returned a completion, truncated or refused ones included. Calls that
+/// failed with an HTTP error are not billed and not recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attempt {
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub latency_ms: u64,
+    /// None when the catalog has no price for the model
+    pub cost_usd: Option<f64>,
+}
+
+impl Attempt {
+    /// "provider:model", matching `IdeaBundle::target`
+    pub fn target(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+}
+
+/// Totals over a set of attempts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Summed over attempts; concurrent calls overlap, so this is more than the wall time
+    pub latency_ms: u64,
+    /// Cost of the priced attempts
+    pub cost_usd: f64,
+    pub attempts: u32,
+    /// Attempts whose model has no catalog price; `cost_usd` leaves them out
+    pub unpriced_attempts: u32,
+}
+
+impl Usage {
+    pub fn from_attempts<'a>(attempts: impl IntoIterator<Item = &'a Attempt>) -> Self {
+        let mut usage = Usage::default();
+        for attempt in attempts {
+            usage.add(attempt);
+        }
+        usage
+    }
+
+    pub fn add(&mut self, attempt: &Attempt) {
+        self.input_tokens += attempt.input_tokens;
+        self.output_tokens += attempt.output_tokens;
+        self.latency_ms += attempt.latency_ms;
+        self.attempts += 1;
+        match attempt.cost_usd {
+            Some(cost) => self.cost_usd += cost,
+            None => self.unpriced_attempts += 1,
+        }
+    }
+}
+
+/// Where `track_into` collects attempts; shared so they outlive a task that panics or is cancelled
+pub type AttemptSink = Arc<Mutex<Vec<Attempt>>>;
+
+tokio::task_local! {
+    static ATTEMPTS: AttemptSink;
+}
+
+/// Run `future` and return what it produced with every attempt recorded inside it. Tasks it spawns
+/// are not covered; they track their own attempts.
+pub async fn track<F: Future>(future: F) -> (F::Output, Vec<Attempt>) {
+    let sink = AttemptSink::default();
+    let output = track_into(Arc::clone(&sink), future).await;
+    let attempts = std::mem::take(&mut *sink.lock().unwrap());
+    (output, attempts)
+}
+
+/// Run `future`, pushing every attempt recorded inside it onto `sink` as it happens
+pub async fn track_into<F: Future>(sink: AttemptSink, future: F) -> F::Output {
+    ATTEMPTS.scope(sink, future).await
+}
+
+/// Record a call started at `started`, priced through the model catalog. Outside `track` this does nothing.
+pub fn record(provider: &str, model: &str, input_tokens: u64, output_tokens: u64, started: Instant) {
+    let attempt = Attempt {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        input_tokens,
+        output_tokens,
+        latency_ms: started.elapsed().as_millis() as u64,
+        cost_usd: catalog::lookup(provider, model).and_then(|m| m.cost_usd(input_tokens, output_tokens)),
+    };
+    let _ = ATTEMPTS.try_with(|attempts| attempts.lock().unwrap().push(attempt));
+}
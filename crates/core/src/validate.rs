@@ -0,0 +1,106 @@
+//! Semantic validation - checks a parsed IdeaBundle is actually usable
+
+use crate::schema::{Complexity, IdeaBundle, Priority, Severity};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    NoIdeas,
+    NoSteps,
+    DuplicateStepOrder(i32),
+    /// Step orders sorted should read 1, 2, 3, ...; the first gap or offset found
+    NonContiguousSteps { expected: i32, found: i32 },
+    UnknownSeverity { risk: usize, value: String },
+    UnknownPriority { action: usize, value: String },
+    UnknownComplexity(String),
+    EmptyEffortTime,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoIdeas => write!(f, "`ideas` is empty"),
+            Self::NoSteps => write!(f, "`step_plan` is empty"),
+            Self::DuplicateStepOrder(order) => write!(f, "more than one step has order {}", order),
+            Self::NonContiguousSteps { expected, found } => write!(
+                f,
+                "step orders must run 1, 2, 3, ... without gaps: expected {}, found {}",
+                expected, found
+            ),
+            Self::UnknownSeverity { risk, value } => write!(
+                f,
+                "risk {} has severity \"{}\"; use one of {}",
+                risk + 1,
+                value,
+                Severity::VALUES.join(", ")
+            ),
+            Self::UnknownPriority { action, value } => write!(
+                f,
+                "next action {} has priority \"{}\"; use one of {}",
+                action + 1,
+                value,
+                Priority::VALUES.join(", ")
+            ),
+            Self::UnknownComplexity(value) => write!(
+                f,
+                "effort complexity \"{}\"; use one of {}",
+                value,
+                Complexity::VALUES.join(", ")
+            ),
+            Self::EmptyEffortTime => write!(f, "`effort.time` is empty"),
+        }
+    }
+}
+
+/// Every violation in the bundle, in schema order. Empty means the bundle is valid.
+pub fn validate(bundle: &IdeaBundle) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if bundle.ideas.is_empty() {
+        violations.push(Violation::NoIdeas);
+    }
+
+    if bundle.step_plan.is_empty() {
+        violations.push(Violation::NoSteps);
+    } else {
+        let mut orders: Vec<i32> = bundle.step_plan.iter().map(|s| s.order).collect();
+        orders.sort_unstable();
+        // One violation per repeated order, however many steps share it
+        let mut duplicates: Vec<i32> = orders.windows(2).filter(|p| p[0] == p[1]).map(|p| p[0]).collect();
+        duplicates.dedup();
+        violations.extend(duplicates.into_iter().map(Violation::DuplicateStepOrder));
+        orders.dedup();
+        if let Some((expected, found)) = (1..).zip(orders.iter().copied()).find(|(e, f)| e != f) {
+            violations.push(Violation::NonContiguousSteps { expected, found });
+        }
+    }
+
+    for (i, risk) in bundle.risks.iter().enumerate() {
+        if !risk.severity.is_known() {
+            violations.push(Violation::UnknownSeverity {
+                risk: i,
+                value: risk.severity.to_string(),
+            });
+        }
+    }
+
+    if bundle.effort.time.trim().is_empty() {
+        violations.push(Violation::EmptyEffortTime);
+    }
+    if let Some(complexity) = &bundle.effort.complexity {
+        if !complexity.is_known() {
+            violations.push(Violation::UnknownComplexity(complexity.to_string()));
+        }
+    }
+
+    for (i, action) in bundle.next_actions.iter().enumerate() {
+        if !action.priority.is_known() {
+            violations.push(Violation::UnknownPriority {
+                action: i,
+                value: action.priority.to_string(),
+            });
+        }
+    }
+
+    violations
+}
@@ -0,0 +1,103 @@
+//! Orchestrator retries against the mock provider: Retry-After is honoured, server errors back off
+
+use idea_engine_core::{CompletionRequest, OpenAIAdapter, Orchestrator};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A mock-provider process on a free port, killed on drop
+struct Mock {
+    child: Child,
+    url: String,
+}
+
+impl Mock {
+    async fn start(script: Value) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mock-provider"))
+            .args(["--port", "0"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("start mock-provider");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+        let url = line
+            .trim()
+            .strip_prefix("mock-provider listening on ")
+            .expect("listening line")
+            .to_string();
+        let mock = Self { child, url };
+        reqwest::Client::new()
+            .post(format!("{}/__mock/script", mock.url))
+            .json(&script)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        mock
+    }
+
+    async fn request_count(&self) -> usize {
+        let requests: Vec<Value> = reqwest::get(format!("{}/__mock/requests", self.url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        requests.len()
+    }
+
+    fn orchestrator(&self) -> Orchestrator {
+        let adapter = OpenAIAdapter::new(Some("mock".into())).with_base_url(&self.url);
+        Orchestrator::new(vec![Arc::new(adapter)]).with_retries(2)
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn request() -> CompletionRequest {
+    CompletionRequest::new("You are a planner.", "Grow a podcast audience")
+}
+
+#[tokio::test]
+async fn waits_for_retry_after_before_retrying() {
+    let mock = Mock::start(json!([{"type": "rate_limit", "retry_after": 1}])).await;
+    let started = Instant::now();
+    let result = mock.orchestrator().run(&request(), None).await;
+
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(result.bundles.len(), 1);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(mock.request_count().await, 2);
+}
+
+#[tokio::test]
+async fn backs_off_between_server_errors() {
+    let mock = Mock::start(json!([{"type": "server_error", "status": 503, "times": 2}])).await;
+    let started = Instant::now();
+    let result = mock.orchestrator().run(&request(), None).await;
+
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(result.bundles.len(), 1);
+    // 500 ms, then 1 s
+    assert!(started.elapsed() >= Duration::from_millis(1500));
+    assert_eq!(mock.request_count().await, 3);
+}
+
+#[tokio::test]
+async fn gives_up_on_a_long_retry_after() {
+    let mock = Mock::start(json!([{"type": "rate_limit", "retry_after": 3600}])).await;
+    let result = mock.orchestrator().run(&request(), None).await;
+
+    assert!(result.bundles.is_empty());
+    assert_eq!(result.errors.len(), 1);
+    assert!(result.errors[0].1.starts_with("Rate limited"), "{}", result.errors[0].1);
+    assert_eq!(mock.request_count().await, 1);
+}
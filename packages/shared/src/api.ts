@@ -2,12 +2,21 @@
  * API client - used when running in browser (not Tauri)
  * Set VITE_API_URL for the deployed backend
  */
+import type { ClarifyingAnswer } from './types';
 
 export const getApiUrl = (override?: string) =>
   override ||
   (typeof import.meta !== 'undefined' && (import.meta as { env?: { VITE_API_URL?: string } }).env?.VITE_API_URL) ||
   'http://localhost:3000';
 
+/** Optional run settings for sending a message */
+export interface SendMessageOptions {
+  /** Split the problem into sub-problems, solve each and assemble one plan */
+  decompose?: boolean;
+  /** Answers to clarifying questions, folded into the problem before it is sent */
+  clarifications?: ClarifyingAnswer[];
+}
+
 export async function apiCreateChat(title: string, templateId?: string, apiUrl?: string): Promise<string> {
   const res = await fetch(`${getApiUrl(apiUrl)}/api/chats`, {
     method: 'POST',
@@ -36,12 +45,20 @@ export async function apiSendMessage(
   systemPrompt: string,
   providers: string[],
   apiKeys: Record<string, string>,
-  apiUrl?: string
+  apiUrl?: string,
+  options?: SendMessageOptions
 ) {
   const res = await fetch(`${getApiUrl(apiUrl)}/api/chats/${chatId}/messages`, {
     method: 'POST',
     headers: { 'Content-Type': 'application/json' },
-    body: JSON.stringify({ content, systemPrompt, providers, apiKeys }),
+    body: JSON.stringify({
+      content,
+      systemPrompt,
+      providers,
+      apiKeys,
+      decompose: options?.decompose ?? false,
+      clarifications: options?.clarifications ?? [],
+    }),
   });
   if (!res.ok) throw new Error(await res.text());
   return res.json();
@@ -0,0 +1,30 @@
+// Generated by `cargo run -p idea-engine-core --bin codegen` from crates/core/src/schema.rs. Do not edit.
+
+import { z } from 'zod';
+
+export const SEVERITIES = ['low', 'medium', 'high'] as const;
+export type KnownSeverity = (typeof SEVERITIES)[number];
+/** A known value, or the unmapped value a provider sent (`Unknown` in Rust) */
+export type Severity = KnownSeverity | (string & {});
+export function isSeverity(value: string): value is KnownSeverity {
+  return (SEVERITIES as readonly string[]).includes(value);
+}
+export const SeveritySchema = z.union([z.enum(SEVERITIES), z.string()]);
+
+export const PRIORITIES = ['immediate', 'short', 'medium', 'long'] as const;
+export type KnownPriority = (typeof PRIORITIES)[number];
+/** A known value, or the unmapped value a provider sent (`Unknown` in Rust) */
+export type Priority = KnownPriority | (string & {});
+export function isPriority(value: string): value is KnownPriority {
+  return (PRIORITIES as readonly string[]).includes(value);
+}
+export const PrioritySchema = z.union([z.enum(PRIORITIES), z.string()]);
+
+export const COMPLEXITIES = ['low', 'medium', 'high'] as const;
+export type KnownComplexity = (typeof COMPLEXITIES)[number];
+/** A known value, or the unmapped value a provider sent (`Unknown` in Rust) */
+export type Complexity = KnownComplexity | (string & {});
+export function isComplexity(value: string): value is KnownComplexity {
+  return (COMPLEXITIES as readonly string[]).includes(value);
+}
+export const ComplexitySchema = z.union([z.enum(COMPLEXITIES), z.string()]);
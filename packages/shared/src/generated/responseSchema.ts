@@ -0,0 +1,160 @@
+// Generated by `cargo run -p idea-engine-core --bin codegen` from crates/core/src/schema.rs. Do not edit.
+
+export const AI_RESPONSE_SCHEMA_VERSION = 1;
+
+export const AI_RESPONSE_JSON_SCHEMA = {
+  "$id": "idea-engine/ai-response/v1",
+  "$schema": "http://json-schema.org/draft-07/schema#",
+  "description": "Raw AI response schema - what we expect from providers",
+  "properties": {
+    "dependencies": {
+      "items": {
+        "type": "string"
+      },
+      "type": "array"
+    },
+    "effort": {
+      "properties": {
+        "complexity": {
+          "enum": [
+            "low",
+            "medium",
+            "high",
+            null
+          ],
+          "type": [
+            "string",
+            "null"
+          ]
+        },
+        "cost": {
+          "type": [
+            "string",
+            "null"
+          ]
+        },
+        "time": {
+          "description": "Rough duration, e.g. \"2 weeks\"",
+          "type": "string"
+        }
+      },
+      "required": [
+        "time"
+      ],
+      "type": "object"
+    },
+    "ideas": {
+      "items": {
+        "properties": {
+          "description": {
+            "type": "string"
+          },
+          "rationale": {
+            "type": [
+              "string",
+              "null"
+            ]
+          },
+          "title": {
+            "type": "string"
+          }
+        },
+        "required": [
+          "title",
+          "description"
+        ],
+        "type": "object"
+      },
+      "type": "array"
+    },
+    "next_actions": {
+      "items": {
+        "properties": {
+          "action": {
+            "type": "string"
+          },
+          "priority": {
+            "enum": [
+              "immediate",
+              "short",
+              "medium",
+              "long"
+            ],
+            "type": "string"
+          }
+        },
+        "required": [
+          "action",
+          "priority"
+        ],
+        "type": "object"
+      },
+      "type": "array"
+    },
+    "risks": {
+      "items": {
+        "properties": {
+          "description": {
+            "type": "string"
+          },
+          "mitigation": {
+            "type": [
+              "string",
+              "null"
+            ]
+          },
+          "severity": {
+            "enum": [
+              "low",
+              "medium",
+              "high"
+            ],
+            "type": "string"
+          }
+        },
+        "required": [
+          "description",
+          "severity"
+        ],
+        "type": "object"
+      },
+      "type": "array"
+    },
+    "step_plan": {
+      "items": {
+        "properties": {
+          "action": {
+            "type": "string"
+          },
+          "details": {
+            "type": [
+              "string",
+              "null"
+            ]
+          },
+          "order": {
+            "description": "1-based position in the plan",
+            "format": "int32",
+            "type": "integer"
+          }
+        },
+        "required": [
+          "order",
+          "action"
+        ],
+        "type": "object"
+      },
+      "type": "array"
+    }
+  },
+  "required": [
+    "ideas",
+    "step_plan",
+    "risks",
+    "dependencies",
+    "effort",
+    "next_actions"
+  ],
+  "title": "AIResponse",
+  "type": "object"
+} as const;
@@ -1,4 +1,6 @@
 export * from './api';
+export * from './generated/enums';
+export * from './generated/responseSchema';
 export * from './schemas';
 export * from './templates/youtube-playlist';
 export * from './types';
@@ -2,6 +2,7 @@
  * Zod schemas for validation
  */
 import { z } from 'zod';
+import { ComplexitySchema, PrioritySchema, SeveritySchema } from './generated/enums';
 
 export const IdeaSchema = z.object({
   title: z.string(),
@@ -17,19 +18,36 @@ export const StepSchema = z.object({
 
 export const RiskSchema = z.object({
   description: z.string(),
-  severity: z.enum(['low', 'medium', 'high']),
+  severity: SeveritySchema,
   mitigation: z.string().optional(),
 });
 
 export const EffortEstimateSchema = z.object({
   time: z.string(),
   cost: z.string().optional(),
-  complexity: z.enum(['low', 'medium', 'high']).optional(),
+  complexity: ComplexitySchema.optional(),
 });
 
 export const NextActionSchema = z.object({
   action: z.string(),
-  priority: z.enum(['immediate', 'short', 'medium', 'long']),
+  priority: PrioritySchema,
+});
+
+export const GenerationParamsSchema = z.object({
+  temperature: z.number().min(0).max(2).optional(),
+  max_tokens: z.number().int().positive().optional(),
+  seed: z.number().int().min(0).optional(),
+  top_p: z.number().min(0).max(1).optional(),
+  stop: z.array(z.string()).optional(),
+});
+
+export const UsageSchema = z.object({
+  input_tokens: z.number().int().min(0),
+  output_tokens: z.number().int().min(0),
+  latency_ms: z.number().int().min(0),
+  cost_usd: z.number().min(0),
+  attempts: z.number().int().min(0),
+  unpriced_attempts: z.number().int().min(0),
 });
 
 export const IdeaBundleSchema = z.object({
@@ -43,6 +61,11 @@ export const IdeaBundleSchema = z.object({
   effort: EffortEstimateSchema,
   nextActions: z.array(NextActionSchema),
   rawResponse: z.string().optional(),
+  warnings: z.array(z.string()).optional(),
+  repairAttempts: z.number().int().min(0).optional(),
+  params: GenerationParamsSchema.optional(),
+  persona: z.string().optional(),
+  usage: UsageSchema.optional(),
   createdAt: z.string(),
 });
 
@@ -3,6 +3,8 @@
  * Used by frontend and Rust backend via IPC
  */
 
+import type { Complexity, Priority, Severity } from './generated/enums';
+
 export interface IdeaBundle {
   id: string;
   provider: 'openai' | 'anthropic' | 'gemini';
@@ -14,9 +16,95 @@ export interface IdeaBundle {
   effort: EffortEstimate;
   nextActions: NextAction[];
   rawResponse?: string;
+  /** Repairs and coercions applied to the provider output, plus semantic violations left unfixed */
+  warnings?: string[];
+  /** Follow-up requests sent to fix semantic violations, whether or not they helped */
+  repairAttempts?: number;
+  /** Generation parameters the provider was called with */
+  params?: GenerationParams;
+  /** Id of the persona the bundle was generated under */
+  persona?: string;
+  /** Tokens, latency and cost of every call behind the bundle, retries and repairs included */
+  usage?: Usage;
   createdAt: string;
 }
 
+/** A system-prompt overlay; send an id for a built-in persona or the full object for a custom one */
+export interface Persona {
+  id: string;
+  name: string;
+  overlay: string;
+}
+
+/** Sampling and length controls; keys match the Rust `GenerationParams` wire format */
+export interface GenerationParams {
+  temperature?: number;
+  max_tokens?: number;
+  seed?: number;
+  top_p?: number;
+  stop?: string[];
+}
+
+/** One provider and model to run; params override the run's params for this target only */
+export interface RunTarget {
+  provider: string;
+  model?: string;
+  params?: GenerationParams;
+}
+
+export type ModelStatus = 'active' | 'deprecated' | 'retired';
+
+/** Model catalog entry; keys match the Rust `ModelInfo` wire format, unknown values are omitted */
+export interface ModelInfo {
+  provider: string;
+  model: string;
+  context_window?: number;
+  max_output_tokens?: number;
+  structured_output?: boolean;
+  streaming?: boolean;
+  input_usd_per_mtok?: number;
+  output_usd_per_mtok?: number;
+  status: ModelStatus;
+  replaced_by?: string;
+}
+
+/** Result of pulling a provider's model list into the catalog */
+export interface ModelRefreshReport {
+  provider: string;
+  added: string[];
+  missing: string[];
+}
+
+/** Token and cost totals; keys match the Rust `Usage` wire format */
+export interface Usage {
+  input_tokens: number;
+  output_tokens: number;
+  /** Summed over calls, so more than the wall time when calls ran concurrently */
+  latency_ms: number;
+  /** USD for the calls whose model has a catalog price */
+  cost_usd: number;
+  attempts: number;
+  /** Calls left out of cost_usd because their model has no price */
+  unpriced_attempts: number;
+}
+
+export type SpendGroup = 'day' | 'provider' | 'model' | 'recipe' | 'chat';
+
+/** Usage summed over one group; key is null for usage without a recipe or chat */
+export interface SpendRow {
+  key: string | null;
+  usage: Usage;
+}
+
+/** What one "provider:model" target produced in a run */
+export interface TargetOutcome {
+  bundleIds: string[];
+  /** Ids of the kept bundles this target's near-duplicates were merged into */
+  mergedInto: string[];
+  errors: string[];
+  usage: Usage;
+}
+
 export interface Idea {
   title: string;
   description: string;
@@ -31,19 +119,19 @@ export interface Step {
 
 export interface Risk {
   description: string;
-  severity: 'low' | 'medium' | 'high';
+  severity: Severity;
   mitigation?: string;
 }
 
 export interface EffortEstimate {
   time: string;
   cost?: string;
-  complexity?: 'low' | 'medium' | 'high';
+  complexity?: Complexity;
 }
 
 export interface NextAction {
   action: string;
-  priority: 'immediate' | 'short' | 'medium' | 'long';
+  priority: Priority;
 }
 
 export interface ScoreCard {
@@ -64,9 +152,67 @@ export interface ChatMessage {
   content: string;
   ideaBundles?: IdeaBundle[];
   feedback?: FeedbackType;
+  /** Set on drill-down messages: the idea they expand */
+  parent?: IdeaRef;
+  /** Set on decomposed answers: how the bundles fit into one plan */
+  plan?: HierarchicalPlan;
   createdAt: string;
 }
 
+export interface SubProblem {
+  id: string;
+  title: string;
+  description: string;
+  /** Ids of sub-problems that must be solved first */
+  dependsOn: string[];
+}
+
+/** One sub-problem with the id of its top-ranked bundle in the message's ideaBundles */
+export interface PlanSection {
+  subProblem: SubProblem;
+  bundleId: string | null;
+  /** [target "provider:model", error] for targets that failed on this sub-problem */
+  errors: [string, string][];
+}
+
+/** A step of the assembled plan, numbered across all sections */
+export interface PlanStep {
+  order: number;
+  subProblemId: string;
+  action: string;
+  details?: string | null;
+}
+
+/** A problem split into sub-problems, solved separately and reassembled */
+export interface HierarchicalPlan {
+  /** In dependency order */
+  sections: PlanSection[];
+  steps: PlanStep[];
+  /** Planner output that was corrected, or why the problem was solved whole */
+  warnings: string[];
+  usage: Usage;
+}
+
+export type ClarifyingAspect = 'goal' | 'budget' | 'timeline' | 'audience' | 'constraints' | 'other';
+
+export interface ClarifyingQuestion {
+  aspect: ClarifyingAspect;
+  question: string;
+  options?: string[];
+}
+
+export interface ClarifyingAnswer {
+  question: string;
+  answer: string;
+}
+
+/** One idea inside a stored message's bundles */
+export interface IdeaRef {
+  messageId: string;
+  bundleId: string;
+  ideaIndex: number;
+}
+
 export interface Chat {
   id: string;
   title: string;
@@ -83,13 +229,85 @@ export interface PromptRecipe {
   userPromptTemplate: string;
   rubric: Partial<ScoreCard>;
   fewShotExamples?: string[];
+  assertions?: Assertion[];
+  params?: GenerationParams;
   createdAt: string;
 }
 
+export type Assertion =
+  | { type: 'must_mention'; pattern: string; regex?: boolean }
+  | { type: 'must_not_mention'; pattern: string; regex?: boolean }
+  | { type: 'min_steps'; count: number }
+  | { type: 'no_unmitigated_high_risk' }
+  | { type: 'requires_dependency'; name: string }
+  | { type: 'valid_json' };
+
+export interface AssertionResult {
+  assertion: Assertion;
+  passed: boolean;
+  detail?: string;
+}
+
 export interface EvalResult {
   recipeId: string;
   problemId: string;
   bundleId: string;
   scoreCard: ScoreCard;
   delta?: Partial<ScoreCard>;
+  assertions?: AssertionResult[];
+}
+
+export interface AbArm {
+  recipeId: string;
+  provider?: string | null;
+  model?: string | null;
+}
+
+export interface DimensionComparison {
+  dimension: keyof ScoreCard;
+  meanA: number;
+  ciA: [number, number];
+  meanB: number;
+  ciB: [number, number];
+  meanDiff: number;
+  ciDiff: [number, number];
+  pValue: number;
+  pAdjusted: number;
+  verdict: 'b_better' | 'a_better' | 'no_difference';
+}
+
+/** Stored A/B comparison of two recipes or models. Tests pair the arms per (problem, repeat). */
+export interface AbComparison {
+  id: string;
+  armA: AbArm;
+  armB: AbArm;
+  options: { repeats: number; alpha: number; bootstrapIterations: number; seed: number };
+  runIdA: string;
+  runIdB: string;
+  pairedProblems: string[];
+  pairs: number;
+  dimensions: DimensionComparison[];
+  /** E.g. too few pairs for any dimension to reach significance */
+  warnings: string[];
+  bBeatsA: boolean;
+  createdAt: string;
+}
+
+export interface AbComparisonSummary {
+  id: string;
+  labelA: string;
+  labelB: string;
+  bBeatsA: boolean;
+  createdAt: string;
+}
+
+/** Judge tournament rating for one recipe @ provider:model contestant */
+export interface LeaderboardEntry {
+  contestant: string;
+  elo: number;
+  bradleyTerry: number;
+  matches: number;
+  wins: number;
+  losses: number;
+  ties: number;
 }